@@ -0,0 +1,167 @@
+/* C interface of the 4x4x4 engine, implemented in src/capi.rs, generated by cbindgen.
+ *
+ * Link with the library built by `cargo build --release`:
+ * target/release/libconnect4x4x4.so (.dylib on macOS, .dll on Windows).
+ *
+ * Columns are x + 4 * y with x and y from 0 to 3, cells hold 1 or -1 for the players
+ * and 0 when empty. The functions returning an int give -1 for invalid arguments,
+ * null pointers included. */
+
+#ifndef CONNECT4X4X4_H
+#define CONNECT4X4X4_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct c4_game c4_game;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ the version of the interface, it changes when a function changes
+ */
+int c4_api_version(void);
+
+/*
+ empty board, player 1 to move
+ */
+c4_game *c4_game_new(void);
+
+/*
+ frees a game of c4_game_new
+
+ # Safety
+
+ `game` must be null or come from c4_game_new and not be freed yet, it cannot be
+ used once freed.
+ */
+void c4_game_free(c4_game *game);
+
+/*
+ options of the command line as one string, for example "--personality aggressive",
+ 0 when they are accepted, -1 for an unknown option
+
+ # Safety
+
+ `game` must be null or come from c4_game_new and not be freed yet.
+ `options` must be null or a string ending with 0.
+ */
+int c4_game_configure(c4_game *game, const char *options);
+
+/*
+ back to the empty board with player 1 to move
+
+ # Safety
+
+ `game` must be null or come from c4_game_new and not be freed yet.
+ */
+int c4_game_reset(c4_game *game);
+
+/*
+ the position from its encoding, the 16 columns x + 4 * y separated by '/', each
+ of 4 cells from bottom to top among '+', '-' and '.', and the player to move
+
+ # Safety
+
+ `game` must be null or come from c4_game_new and not be freed yet.
+ `encoding` must be null or a string ending with 0.
+ */
+int c4_game_set(c4_game *game, const char *encoding, int player);
+
+/*
+ writes the encoding of the position and a final 0 into `buffer` of `size` bytes,
+ returns the length of the encoding, nothing is written when it does not fit
+
+ # Safety
+
+ `game` must be null or come from c4_game_new and not be freed yet.
+ `buffer` must be null or hold `size` bytes.
+ */
+int c4_game_encode(c4_game *game, char *buffer, size_t size);
+
+/*
+ the player to move, 1 or -1
+
+ # Safety
+
+ `game` must be null or come from c4_game_new and not be freed yet.
+ */
+int c4_game_player(c4_game *game);
+
+/*
+ the cell, 0 out of the board
+
+ # Safety
+
+ `game` must be null or come from c4_game_new and not be freed yet.
+ */
+int c4_game_get(c4_game *game, int x, int y, int z);
+
+/*
+ 1 when the player to move can play in the column
+
+ # Safety
+
+ `game` must be null or come from c4_game_new and not be freed yet.
+ */
+int c4_game_playable(c4_game *game, int col);
+
+/*
+ plays in the column for the player to move, 1 when played, 0 when illegal
+
+ # Safety
+
+ `game` must be null or come from c4_game_new and not be freed yet.
+ */
+int c4_game_play(c4_game *game, int col);
+
+/*
+ takes back the last move, 0 when there is none
+
+ # Safety
+
+ `game` must be null or come from c4_game_new and not be freed yet.
+ */
+int c4_game_undo(c4_game *game);
+
+/*
+ 1 when `player` has a line of four
+
+ # Safety
+
+ `game` must be null or come from c4_game_new and not be freed yet.
+ */
+int c4_game_win(c4_game *game, int player);
+
+/*
+ 1 when the board is full
+
+ # Safety
+
+ `game` must be null or come from c4_game_new and not be freed yet.
+ */
+int c4_game_full(c4_game *game);
+
+/*
+ best column for the player to move, searched until `depth` or for `movetime`
+ milliseconds. A depth of 0 searches as deep as the movetime allows, or to depth 6
+ when the movetime is 0 too. The value in its perspective is written to `value`
+ when not null. -1 when the game is over.
+
+ # Safety
+
+ `game` must be null or come from c4_game_new and not be freed yet.
+ `value` must be null or point to an int.
+ */
+int c4_search(c4_game *game, int depth, int movetime, int *value);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* CONNECT4X4X4_H */
@@ -0,0 +1,299 @@
+use connect4x4x4::negamax::GameState;
+use connect4x4x4::search;
+use connect4x4x4::state;
+use connect4x4x4::time;
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+// Python module connect4x4x4
+//
+// State holds a position with the rules of the game, Search is the engine. Columns
+// are x + 4 * y with x and y from 0 to 3, cells hold 1 or -1 for the players and 0
+// when empty, the players are 1 and -1.
+
+fn check_player(player: i32) -> PyResult<()> {
+    match player {
+        1 | -1 => Ok(()),
+        _ => Err(PyValueError::new_err(format!(
+            "player must be 1 or -1, got {}",
+            player
+        ))),
+    }
+}
+
+fn check_col(col: usize) -> PyResult<()> {
+    if col < 16 {
+        Ok(())
+    } else {
+        Err(PyIndexError::new_err(format!(
+            "column must be in 0..16, got {}",
+            col
+        )))
+    }
+}
+
+/// A position: State() is the empty board, State(encoding) reads the encoding of
+/// encode(), the 16 columns separated by '/', each of 4 cells from bottom to top
+/// among '+', '-' and '.'.
+#[pyclass(name = "State", module = "connect4x4x4")]
+#[derive(Clone)]
+struct PyState {
+    x: state::State,
+}
+
+#[pymethods]
+impl PyState {
+    #[new]
+    #[pyo3(signature = (encoding = None))]
+    fn new(encoding: Option<&str>) -> PyResult<PyState> {
+        let x = match encoding {
+            Some(s) => s.parse().map_err(PyValueError::new_err)?,
+            None => state::State::new(),
+        };
+        Ok(PyState { x })
+    }
+
+    /// the cell at (x, y, z), z from the bottom
+    fn get(&self, x: usize, y: usize, z: usize) -> PyResult<i32> {
+        if x > 3 || y > 3 || z > 3 {
+            return Err(PyIndexError::new_err("coordinates must be in 0..4"));
+        }
+        Ok(self.x.get(x, y, z))
+    }
+
+    /// the 64 cells, index x + 4 * y + 16 * z
+    fn cells(&self) -> Vec<i32> {
+        self.x.cells().to_vec()
+    }
+
+    /// drops a piece of `player` in the column (x, y), False when it is full
+    fn add(&mut self, x: usize, y: usize, player: i32) -> PyResult<bool> {
+        check_player(player)?;
+        if x > 3 || y > 3 {
+            return Err(PyIndexError::new_err("coordinates must be in 0..4"));
+        }
+        Ok(self.x.add(x, y, player))
+    }
+
+    /// same as add with the column x + 4 * y
+    fn play(&mut self, col: usize, player: i32) -> PyResult<bool> {
+        check_player(player)?;
+        check_col(col)?;
+        Ok(self.x.play(col, player))
+    }
+
+    fn playable(&self, col: usize) -> PyResult<bool> {
+        check_col(col)?;
+        Ok(self.x.playable(col))
+    }
+
+    /// the columns that are not full, none once a player has won
+    fn legal_moves(&self) -> Vec<usize> {
+        if self.x.win(1) || self.x.win(-1) {
+            return Vec::new();
+        }
+        (0..16).filter(|&col| self.x.playable(col)).collect()
+    }
+
+    /// True when `player` has a line of four
+    fn win(&self, player: i32) -> PyResult<bool> {
+        check_player(player)?;
+        Ok(self.x.win(player))
+    }
+
+    fn full(&self) -> bool {
+        self.x.full()
+    }
+
+    /// number of empty cells
+    fn empty(&self) -> usize {
+        self.x.empty()
+    }
+
+    /// the static evaluation of the engine, in the perspective of player 1
+    fn value(&self) -> i32 {
+        self.x.value()
+    }
+
+    /// the values of the lines still open to 1 and to -1, value() is their difference
+    fn lines(&self) -> (i32, i32) {
+        self.x.lines()
+    }
+
+    /// the image by one of the 8 symmetries of the board, 0 being the identity
+    fn symmetry(&self, id: usize) -> PyResult<PyState> {
+        if id >= 8 {
+            return Err(PyIndexError::new_err("symmetry must be in 0..8"));
+        }
+        Ok(PyState {
+            x: self.x.symmetry(id),
+        })
+    }
+
+    /// the images by the 8 symmetries, in the order of their ids
+    fn symmetries(&self) -> Vec<PyState> {
+        self.x
+            .symmetries()
+            .into_iter()
+            .map(|x| PyState { x })
+            .collect()
+    }
+
+    /// the representative up to the symmetries and the id of the symmetry leading to it
+    fn canonical(&self) -> (PyState, usize) {
+        let (x, id) = self.x.canonical();
+        (PyState { x }, id)
+    }
+
+    fn encode(&self) -> String {
+        self.x.encode()
+    }
+
+    fn copy(&self) -> PyState {
+        self.clone()
+    }
+
+    fn __copy__(&self) -> PyState {
+        self.clone()
+    }
+
+    fn __str__(&self) -> String {
+        self.x.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("State('{}')", self.x.encode())
+    }
+
+    fn __eq__(&self, other: &PyState) -> bool {
+        self.x == other.x
+    }
+
+    fn __hash__(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.x.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// The engine: Search(options) takes the options of the command line as one string,
+/// for example "--personality aggressive", an unknown option raises ValueError. It keeps
+/// its table between searches, clear() forgets it for a new game.
+#[pyclass(name = "Search", module = "connect4x4x4")]
+struct PySearch {
+    search: search::Search,
+}
+
+#[pymethods]
+impl PySearch {
+    #[new]
+    #[pyo3(signature = (options = ""))]
+    fn new(options: &str) -> PyResult<PySearch> {
+        let mut search = search::Search::new();
+        search
+            .configure_options(options, &[])
+            .map_err(PyValueError::new_err)?;
+        Ok(PySearch { search })
+    }
+
+    /// the best column for `player` and its value in their perspective, searched until
+    /// `depth` or for `movetime` seconds, None when there is no move
+    #[pyo3(signature = (state, player, depth = None, movetime = None))]
+    fn think(
+        &mut self,
+        py: Python,
+        state: &PyState,
+        player: i32,
+        depth: Option<u32>,
+        movetime: Option<f64>,
+    ) -> PyResult<Option<(usize, i32)>> {
+        check_player(player)?;
+        if state.x.win(1) || state.x.win(-1) {
+            return Ok(None);
+        }
+        let depth = match (depth, movetime) {
+            (Some(d), _) => d,
+            (None, Some(_)) => state.x.empty() as u32,
+            (None, None) => search::DEPTH,
+        };
+        let search = &mut self.search;
+        let x = &state.x;
+        Ok(py.allow_threads(move || {
+            search.deadline = movetime.map(|t| time::precise_time_s() + t);
+            let best = search.think(x, player, depth);
+            search.deadline = None;
+            best
+        }))
+    }
+
+    /// every legal column with its value for `player`, best first
+    #[pyo3(signature = (state, player, depth = search::DEPTH))]
+    fn analyse(
+        &mut self,
+        py: Python,
+        state: &PyState,
+        player: i32,
+        depth: u32,
+    ) -> PyResult<Vec<(usize, i32)>> {
+        check_player(player)?;
+        if state.x.win(1) || state.x.win(-1) {
+            return Ok(Vec::new());
+        }
+        let search = &mut self.search;
+        let x = &state.x;
+        Ok(py.allow_threads(move || search.analyse(x, player, depth)))
+    }
+
+    fn clear(&mut self) {
+        self.search.clear();
+    }
+
+    /// nodes searched since the creation
+    #[getter]
+    fn nodes(&self) -> u64 {
+        self.search.nodes
+    }
+}
+
+/// the column of a move in the "xy" notation, 11 to 44, None when it is not one
+#[pyfunction]
+fn parse_move(s: &str) -> Option<usize> {
+    state::parse_move(s)
+}
+
+#[pyfunction]
+fn format_move(col: usize) -> PyResult<String> {
+    check_col(col)?;
+    Ok(state::format_move(col))
+}
+
+/// column `col` of state.symmetry(id) is column symmetry_column(id, col) of state
+#[pyfunction]
+fn symmetry_column(id: usize, col: usize) -> PyResult<usize> {
+    check_col(col)?;
+    if id >= 8 {
+        return Err(PyIndexError::new_err("symmetry must be in 0..8"));
+    }
+    Ok(state::symmetry_column(id, col))
+}
+
+/// a value of the search in words, "wins in N" when it is a forced win
+#[pyfunction]
+fn describe(value: i32) -> String {
+    search::describe(value)
+}
+
+/// Rules and engine of the 4x4x4 connect four
+#[pymodule]
+#[pyo3(name = "connect4x4x4")]
+fn bindings(m: &Bound<PyModule>) -> PyResult<()> {
+    m.add_class::<PyState>()?;
+    m.add_class::<PySearch>()?;
+    m.add_function(wrap_pyfunction!(parse_move, m)?)?;
+    m.add_function(wrap_pyfunction!(format_move, m)?)?;
+    m.add_function(wrap_pyfunction!(symmetry_column, m)?)?;
+    m.add_function(wrap_pyfunction!(describe, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,288 @@
+use json;
+use json::Value;
+use negamax::GameState;
+use search;
+use search::Search;
+use state;
+use state::State;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use time;
+
+// Programmatic play and analysis, one JSON object per request and per response
+//
+// Requests carry a "cmd" and its arguments, an optional "id" is copied to the response:
+// {"cmd":"new"}                              new game, + to move
+// {"cmd":"new","state":STATE,"player":"-"}   from a State encoding
+// {"cmd":"play","move":"23"}
+// {"cmd":"undo"}
+// {"cmd":"history"}                          the starting position and the moves since
+// {"cmd":"legal"}                            the legal moves
+// {"cmd":"evaluate"}                         value of the lines for the player to move
+// {"cmd":"search","depth":6,"movetime":500}  best move, both limits are optional,
+//                                            with "play":true the move is also played
+// {"cmd":"threats"}                          moves winning on the spot for each player
+// {"cmd":"state"}
+//
+// Responses have "ok", then either "error" or the position after the request: "state"
+// (the State encoding), "player" to move ("+" or "-") and "result" ("+", "-", "draw"
+// or null while the game goes on), followed by the fields of the command.
+
+pub struct Session {
+    x: State,
+    player: i32,
+    start: (State, i32),
+    // positions before each move, for undo
+    history: Vec<(State, i32, usize)>,
+    pub search: Search,
+}
+
+fn side(player: i32) -> &'static str {
+    if player == 1 {
+        "+"
+    } else {
+        "-"
+    }
+}
+
+fn moves(cols: Vec<usize>) -> Value {
+    cols.into_iter()
+        .map(state::format_move)
+        .collect::<Vec<String>>()
+        .into()
+}
+
+impl Session {
+    // the search gets a stop flag of its own, a clone of a template would share it
+    // with the other sessions
+    pub fn new(mut search: Search) -> Session {
+        search.stop = Arc::new(AtomicBool::new(false));
+        Session {
+            x: State::new(),
+            player: 1,
+            start: (State::new(), 1),
+            history: Vec::new(),
+            search,
+        }
+    }
+
+    pub fn state(&self) -> &State {
+        &self.x
+    }
+
+    pub fn player(&self) -> i32 {
+        self.player
+    }
+
+    fn play(&mut self, col: usize) {
+        self.history.push((self.x.clone(), self.player, col));
+        self.x.play(col, self.player);
+        self.player = -self.player;
+    }
+
+    fn result(&self) -> Value {
+        if self.x.win(1) {
+            "+".into()
+        } else if self.x.win(-1) {
+            "-".into()
+        } else if self.x.full() {
+            "draw".into()
+        } else {
+            Value::Null
+        }
+    }
+
+    fn over(&self) -> bool {
+        self.result() != Value::Null
+    }
+
+    // a response line for a request line
+    pub fn handle_line(&mut self, line: &str) -> String {
+        match json::parse(line) {
+            Ok(request) => self.handle(&request).to_string(),
+            Err(e) => json::object(vec![
+                ("ok", false.into()),
+                ("error", format!("invalid JSON: {}", e).into()),
+            ])
+            .to_string(),
+        }
+    }
+
+    pub fn handle(&mut self, request: &Value) -> Value {
+        let result = self.command(request);
+        self.respond(request, result)
+    }
+
+    // same as handle for a search, `report` receives after each iteration an object
+    // with its depth, value, score, nodes and pv
+    pub fn search<F: FnMut(Value)>(&mut self, request: &Value, report: F) -> Value {
+        let result = self.search_command(request, report);
+        self.respond(request, result)
+    }
+
+    fn respond(&self, request: &Value, result: Result<Vec<(&str, Value)>, String>) -> Value {
+        let mut fields = Vec::new();
+        if let Some(id) = request.get("id") {
+            fields.push(("id", id.clone()));
+        }
+        match result {
+            Ok(r) => {
+                fields.push(("ok", true.into()));
+                fields.push(("state", self.x.encode().into()));
+                fields.push(("player", side(self.player).into()));
+                fields.push(("result", self.result()));
+                fields.extend(r);
+            }
+            Err(e) => {
+                fields.push(("ok", false.into()));
+                fields.push(("error", e.into()));
+            }
+        }
+        json::object(fields)
+    }
+
+    fn command(&mut self, request: &Value) -> Result<Vec<(&'static str, Value)>, String> {
+        let cmd = request
+            .get("cmd")
+            .and_then(Value::as_str)
+            .ok_or("missing cmd")?;
+        match cmd {
+            "new" => {
+                let x = match request.get("state") {
+                    Some(s) => s.as_str().ok_or("state must be a string")?.parse()?,
+                    None => State::new(),
+                };
+                let player = match request.get("player").map(Value::as_str) {
+                    None | Some(Some("+")) => 1,
+                    Some(Some("-")) => -1,
+                    _ => return Err("player must be \"+\" or \"-\"".to_string()),
+                };
+                self.start = (x.clone(), player);
+                self.x = x;
+                self.player = player;
+                self.history.clear();
+                self.search.clear();
+                Ok(vec![])
+            }
+            "play" => {
+                let m = request
+                    .get("move")
+                    .and_then(Value::as_str)
+                    .ok_or("play needs a move")?;
+                let col = state::parse_move(m).ok_or(format!("invalid move {}", m))?;
+                if self.over() || !self.x.playable(col) {
+                    return Err(format!("illegal move {}", m));
+                }
+                self.play(col);
+                Ok(vec![])
+            }
+            "undo" => {
+                let (x, player, _) = self.history.pop().ok_or("nothing to undo")?;
+                self.x = x;
+                self.player = player;
+                Ok(vec![])
+            }
+            "history" => Ok(vec![
+                ("start", self.start.0.encode().into()),
+                ("first", side(self.start.1).into()),
+                ("moves", moves(self.history.iter().map(|h| h.2).collect())),
+            ]),
+            "legal" => {
+                let legal = if self.over() {
+                    vec![]
+                } else {
+                    (0..16).filter(|&col| self.x.playable(col)).collect()
+                };
+                Ok(vec![("moves", moves(legal))])
+            }
+            "evaluate" => {
+                let (plus, minus) = self.x.lines();
+                Ok(vec![
+                    ("value", (self.player * (plus - minus)).into()),
+                    (
+                        "lines",
+                        json::object(vec![("+", plus.into()), ("-", minus.into())]),
+                    ),
+                ])
+            }
+            "threats" => {
+                let mut threats = Vec::new();
+                for &player in [1, -1].iter() {
+                    let cols = (0..16)
+                        .filter(|&col| {
+                            let mut y = self.x.clone();
+                            y.play(col, player) && y.win(player)
+                        })
+                        .collect();
+                    threats.push((side(player), moves(cols)));
+                }
+                Ok(vec![("threats", json::object(threats))])
+            }
+            "search" => self.search_command(request, |_| ()),
+            "state" => Ok(vec![]),
+            c => Err(format!("unknown cmd {}", c)),
+        }
+    }
+
+    fn search_command<F: FnMut(Value)>(
+        &mut self,
+        request: &Value,
+        mut report: F,
+    ) -> Result<Vec<(&'static str, Value)>, String> {
+        if self.over() {
+            return Err("the game is over".to_string());
+        }
+        let depth = match request.get("depth") {
+            Some(d) => d
+                .as_u64()
+                .ok_or("depth must be a non-negative integer")?
+                .min(u64::from(u32::MAX)) as u32,
+            None => search::DEPTH,
+        };
+        let deadline = match request.get("movetime") {
+            Some(ms) => {
+                let ms = ms.as_f64().ok_or("movetime must be a number")?;
+                Some(time::precise_time_s() + ms / 1000.0)
+            }
+            None => None,
+        };
+        let depth = match (request.get("depth"), deadline) {
+            (None, Some(_)) => self.x.empty() as u32,
+            _ => depth,
+        };
+
+        let x = self.x.clone();
+        let player = self.player;
+        self.search.nodes = 0;
+        self.search.deadline = deadline;
+        let t0 = time::precise_time_s();
+        let best = self.search.iterate(&x, player, depth, |s, col, value| {
+            report(json::object(vec![
+                ("depth", s.depth.into()),
+                ("value", value.into()),
+                ("score", search::describe(value).into()),
+                ("nodes", s.nodes.into()),
+                ("seconds", (time::precise_time_s() - t0).into()),
+                ("pv", moves(s.pv(&x, player, col))),
+            ]));
+        });
+        let t1 = time::precise_time_s();
+        self.search.deadline = None;
+
+        let (col, value) = best.ok_or("no legal move")?;
+        let pv = self.search.pv(&x, player, col);
+        if request.get("play") == Some(&Value::Bool(true)) {
+            self.play(col);
+        }
+        Ok(vec![
+            ("move", state::format_move(col).into()),
+            ("value", value.into()),
+            ("score", search::describe(value).into()),
+            ("mate", search::mate_moves(value).into()),
+            ("depth", self.search.depth.into()),
+            ("nodes", self.search.nodes.into()),
+            ("seconds", (t1 - t0).into()),
+            ("pv", moves(pv)),
+        ])
+    }
+}
@@ -0,0 +1,497 @@
+use bench;
+use bench::Position;
+use negamax::GameState;
+use search::Search;
+use state;
+use state::State;
+use std;
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use time;
+
+// Games between engines, for the match and tournament binaries
+//
+// An engine is either a Search of this process, given by the options of the other
+// binaries (`--personality aggressive --lmr 0`) with `--depth N` or `--movetime MS`,
+// or a program speaking the protocol of main_engine.rs, given as `cmd:PROGRAM ARGS`.
+// Its moves are then limited by the depth and movetime of the match.
+
+// depth when no limit is given
+pub const DEPTH: u32 = 5;
+
+#[derive(Clone, Copy)]
+pub struct Limits {
+    pub depth: Option<u32>,
+    // milliseconds
+    pub movetime: Option<f64>,
+}
+
+impl Limits {
+    // `--depth N` and `--movetime MS` of `args`, the others are left to Search::configure
+    pub fn from_args(args: &[String], default: Limits) -> Result<Limits, String> {
+        let value = |flag: &str| args.iter().position(|a| a == flag).map(|i| args.get(i + 1));
+        let mut limits = default;
+        if let Some(d) = value("--depth") {
+            limits.depth = Some(
+                d.and_then(|d| d.parse().ok())
+                    .ok_or("--depth needs a number")?,
+            );
+        }
+        if let Some(ms) = value("--movetime") {
+            limits.movetime = Some(
+                ms.and_then(|ms| ms.parse().ok())
+                    .ok_or("--movetime needs milliseconds")?,
+            );
+        }
+        Ok(limits)
+    }
+
+    // without depth, a movetime searches as deep as it can; Search keeps the depth
+    // within the empty cells
+    fn depth(&self, x: &State) -> u32 {
+        match (self.depth, self.movetime) {
+            (Some(depth), _) => depth,
+            (None, Some(_)) => x.empty() as u32,
+            (None, None) => DEPTH,
+        }
+    }
+}
+
+// a game from an opening, the moves are played from `start`
+#[derive(Clone)]
+pub struct Game {
+    pub start: State,
+    pub first: i32,
+    pub moves: Vec<usize>,
+    // +1 or -1 for the winner, 0 for a draw
+    pub result: i32,
+    // set when the game ended on a failure of an engine
+    pub reason: Option<String>,
+}
+
+impl Game {
+    pub fn new(opening: &Position) -> Game {
+        Game {
+            start: opening.state.clone(),
+            first: opening.player,
+            moves: Vec::new(),
+            result: 0,
+            reason: None,
+        }
+    }
+
+    pub fn position(&self) -> State {
+        let mut x = self.start.clone();
+        let mut player = self.first;
+        for &col in self.moves.iter() {
+            x.play(col, player);
+            player = -player;
+        }
+        x
+    }
+
+    pub fn player(&self) -> i32 {
+        if self.moves.len().is_multiple_of(2) {
+            self.first
+        } else {
+            -self.first
+        }
+    }
+
+    // "1-0" when + wins, "0-1" when - wins, "1/2-1/2"
+    pub fn score(&self) -> &'static str {
+        match self.result {
+            1 => "1-0",
+            -1 => "0-1",
+            _ => "1/2-1/2",
+        }
+    }
+
+    // the record of the game, close to PGN: the tags, the opening and the result,
+    // then the moves after a blank line
+    pub fn record(&self, tags: &[(&str, String)]) -> String {
+        let mut r = String::new();
+        for (name, value) in tags.iter() {
+            r.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "'")));
+        }
+        r.push_str(&format!("[Start \"{}\"]\n", self.start.encode()));
+        r.push_str(&format!("[First \"{}\"]\n", side(self.first)));
+        r.push_str(&format!("[Result \"{}\"]\n", self.score()));
+        if let Some(ref reason) = self.reason {
+            r.push_str(&format!("[Reason \"{}\"]\n", reason.replace('"', "'")));
+        }
+        r.push('\n');
+        let mut moves: Vec<String> = self.moves.iter().map(|&c| state::format_move(c)).collect();
+        moves.push(self.score().to_string());
+        r.push_str(&moves.join(" "));
+        r.push_str("\n\n");
+        r
+    }
+}
+
+fn side(player: i32) -> &'static str {
+    if player == 1 {
+        "+"
+    } else {
+        "-"
+    }
+}
+
+pub enum Engine {
+    Local(Box<Search>, Limits),
+    External(External, Limits),
+}
+
+impl Engine {
+    pub fn from_spec(spec: &str, limits: Limits) -> Result<Engine, String> {
+        if let Some(command) = spec.strip_prefix("cmd:") {
+            return Ok(Engine::External(External::start(command)?, limits));
+        }
+        let mut search = Search::new();
+        let args = search.configure_options(spec, &["--depth", "--movetime"])?;
+        let limits = Limits::from_args(&args, limits)?;
+        Ok(Engine::Local(Box::new(search), limits))
+    }
+
+    pub fn new_game(&mut self) -> Result<(), String> {
+        match *self {
+            Engine::Local(ref mut search, _) => {
+                search.clear();
+                Ok(())
+            }
+            Engine::External(ref mut e, _) => e.send("newgame"),
+        }
+    }
+
+    pub fn best_move(&mut self, game: &Game) -> Result<usize, String> {
+        let x = game.position();
+        match *self {
+            Engine::Local(ref mut search, limits) => {
+                search.deadline = limits
+                    .movetime
+                    .map(|ms| time::precise_time_s() + ms / 1000.0);
+                let best = search.think(&x, game.player(), limits.depth(&x));
+                search.deadline = None;
+                best.map(|(col, _)| col).ok_or("no move".to_string())
+            }
+            Engine::External(ref mut e, limits) => {
+                let mut position = format!(
+                    "position state {} {}",
+                    game.start.encode(),
+                    side(game.first)
+                );
+                if !game.moves.is_empty() {
+                    position.push_str(" moves");
+                    for &col in game.moves.iter() {
+                        position.push(' ');
+                        position.push_str(&state::format_move(col));
+                    }
+                }
+                e.send(&position)?;
+                let mut go = format!("go depth {}", limits.depth(&x));
+                if let Some(ms) = limits.movetime {
+                    go.push_str(&format!(" movetime {}", ms as u64));
+                }
+                e.send(&go)?;
+                let line = e.wait("bestmove")?;
+                let m = line.split_whitespace().nth(1).unwrap_or("none");
+                state::parse_move(m).ok_or(format!("bestmove {}", m))
+            }
+        }
+    }
+}
+
+// an engine running as a child process
+pub struct External {
+    child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+}
+
+impl External {
+    pub fn start(command: &str) -> Result<External, String> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("cmd: needs a program")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("{}: {}", program, e))?;
+        let input = child.stdin.take().expect("piped stdin");
+        let output = BufReader::new(child.stdout.take().expect("piped stdout"));
+        let mut e = External {
+            child,
+            input,
+            output,
+        };
+        e.send("uci")?;
+        e.wait("uciok")?;
+        Ok(e)
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.input, "{}", line)
+            .and_then(|_| self.input.flush())
+            .map_err(|e| format!("engine: {}", e))
+    }
+
+    // the first line starting with `word`, the others are skipped
+    fn wait(&mut self, word: &str) -> Result<String, String> {
+        loop {
+            let mut line = String::new();
+            match self.output.read_line(&mut line) {
+                Ok(0) => return Err("the engine exited".to_string()),
+                Ok(_) => {
+                    if line.split_whitespace().next() == Some(word) {
+                        return Ok(line.trim().to_string());
+                    }
+                }
+                Err(e) => return Err(format!("engine: {}", e)),
+            }
+        }
+    }
+}
+
+impl Drop for External {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.wait();
+    }
+}
+
+// the game lost by `player` on a failure of its engine
+fn forfeit(mut game: Game, player: i32, reason: String) -> Game {
+    game.result = -player;
+    game.reason = Some(format!("{} {}", side(player), reason));
+    game
+}
+
+// plays `plus` against `minus` from the opening, an engine failing or playing an
+// illegal move loses the game
+pub fn play(plus: &mut Engine, minus: &mut Engine, opening: &Position) -> Game {
+    let mut game = Game::new(opening);
+    if let Err(e) = plus.new_game() {
+        return forfeit(game, 1, e);
+    }
+    if let Err(e) = minus.new_game() {
+        return forfeit(game, -1, e);
+    }
+    let mut x = game.position();
+    while !x.win(1) && !x.win(-1) && !x.full() {
+        let player = game.player();
+        let engine = if player == 1 { &mut *plus } else { &mut *minus };
+        let col = match engine.best_move(&game) {
+            Ok(col) if x.playable(col) => col,
+            Ok(col) => {
+                let e = format!("illegal move {}", state::format_move(col));
+                return forfeit(game, player, e);
+            }
+            Err(e) => return forfeit(game, player, e),
+        };
+        x.play(col, player);
+        game.moves.push(col);
+    }
+    game.result = if x.win(1) {
+        1
+    } else if x.win(-1) {
+        -1
+    } else {
+        0
+    };
+    game
+}
+
+// the positions after `plies` moves from the empty board, one per class of symmetry
+pub fn openings(plies: usize) -> Vec<Position> {
+    let mut positions = BTreeSet::new();
+    positions.insert(State::new());
+    let mut player = 1;
+    for _ in 0..plies {
+        let mut next = BTreeSet::new();
+        for x in positions.iter() {
+            for col in 0..16 {
+                let mut y = x.clone();
+                if y.play(col, player) && !y.win(player) {
+                    next.insert(y.canonical().0);
+                }
+            }
+        }
+        positions = next;
+        player = -player;
+    }
+    positions
+        .into_iter()
+        .map(|state| Position {
+            state,
+            player,
+            best: None,
+        })
+        .collect()
+}
+
+// `--openings FILE` in the format of bench/positions.txt, else the openings of
+// `--plies N` moves, 2 by default
+pub fn openings_from_args(args: &[String]) -> Result<Vec<Position>, String> {
+    let openings = match args.iter().position(|a| a == "--openings") {
+        Some(i) => {
+            let path = args.get(i + 1).ok_or("--openings needs a file")?;
+            let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            bench::parse(&text)?
+        }
+        None => openings(option(args, "--plies", 2)?),
+    };
+    if openings.is_empty() {
+        return Err("no opening".to_string());
+    }
+    Ok(openings)
+}
+
+// the number following `flag` in `args`, `default` without the flag
+pub fn option<T: std::str::FromStr>(args: &[String], flag: &str, default: T) -> Result<T, String> {
+    match args.iter().position(|a| a == flag) {
+        Some(i) => args
+            .get(i + 1)
+            .and_then(|v| v.parse().ok())
+            .ok_or(format!("{} needs a number", flag)),
+        None => Ok(default),
+    }
+}
+
+// wins, draws and losses of one engine against another
+#[derive(Clone, Copy, Default)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+// counted for an outcome never seen, so that the variance is never 0, as fishtest does
+const PSEUDO_COUNT: f64 = 1e-3;
+// the scores given to elo() stay within [MIN_SCORE, 1 - MIN_SCORE]
+const MIN_SCORE: f64 = 1e-6;
+
+// expected score for an Elo difference
+fn expected(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+// Elo difference for an expected score, kept finite for a clean sweep
+fn elo(score: f64) -> f64 {
+    let score = score.clamp(MIN_SCORE, 1.0 - MIN_SCORE);
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+impl Score {
+    // 1 win, 0 draw, -1 loss
+    pub fn add(&mut self, result: i32) {
+        match result {
+            1 => self.wins += 1,
+            0 => self.draws += 1,
+            _ => self.losses += 1,
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + 0.5 * self.draws as f64
+    }
+
+    // mean and variance of the points of one game, the outcomes not seen yet being
+    // given PSEUDO_COUNT games
+    fn moments(&self) -> (f64, f64) {
+        let count = |n: u32| if n == 0 { PSEUDO_COUNT } else { n as f64 };
+        let (w, d, l) = (count(self.wins), count(self.draws), count(self.losses));
+        let n = w + d + l;
+        let s = (w + 0.5 * d) / n;
+        let var = (w * (1.0 - s).powi(2) + d * (0.5 - s).powi(2) + l * s.powi(2)) / n;
+        (s, var)
+    }
+
+    // Elo difference and the half width of its 95% confidence interval
+    pub fn elo(&self) -> (f64, f64) {
+        let (s, var) = self.moments();
+        let margin = 1.96 * (var / self.games().max(1) as f64).sqrt();
+        let low = elo(s - margin);
+        let high = elo(s + margin);
+        (elo(s), (high - low) / 2.0)
+    }
+
+    // log-likelihood ratio of the hypothesis elo1 against elo0, normal approximation
+    // of the trinomial as done by fishtest
+    pub fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        let (s, var) = self.moments();
+        let (s0, s1) = (expected(elo0), expected(elo1));
+        self.games() as f64 * (s1 - s0) * (2.0 * s - s0 - s1) / (2.0 * var)
+    }
+}
+
+// sequential probability ratio test of elo0 against elo1 with the error rates alpha and beta
+#[derive(Clone, Copy)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    // the bounds of the log-likelihood ratio
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    // Some(true) when elo1 is accepted, Some(false) for elo0, None to go on
+    pub fn verdict(&self, score: &Score) -> Option<bool> {
+        let llr = score.llr(self.elo0, self.elo1);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            Some(true)
+        } else if llr <= lower {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_sweep() {
+        let sprt = Sprt {
+            elo0: 0.0,
+            elo1: 20.0,
+            alpha: 0.05,
+            beta: 0.05,
+        };
+        let wins = Score {
+            wins: 200,
+            draws: 0,
+            losses: 0,
+        };
+        assert_eq!(sprt.verdict(&wins), Some(true));
+        let (e, margin) = wins.elo();
+        assert!(e.is_finite() && e > 0.0 && margin.is_finite());
+
+        let losses = Score {
+            wins: 0,
+            draws: 0,
+            losses: 200,
+        };
+        assert_eq!(sprt.verdict(&losses), Some(false));
+        let (e, margin) = losses.elo();
+        assert!(e.is_finite() && e < 0.0 && margin.is_finite());
+
+        assert_eq!(sprt.verdict(&Score::default()), None);
+    }
+}
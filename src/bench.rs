@@ -0,0 +1,168 @@
+use search;
+use search::Search;
+use state;
+use state::State;
+use time;
+
+// Fixed suite of positions to measure the speed of the search and to check
+// that changes to the engine do not change its choices.
+//
+// One position per line: the State encoding, the player to move (+ or -) and
+// the best move found at the default depth, `#` starts a comment.
+
+pub static SUITE: &str = include_str!("../bench/positions.txt");
+
+#[derive(Clone)]
+pub struct Position {
+    pub state: State,
+    pub player: i32,
+    pub best: Option<usize>,
+}
+
+pub fn parse(text: &str) -> Result<Vec<Position>, String> {
+    let mut r = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 2 {
+            return Err(format!("line {}: expected a position and a player", n + 1));
+        }
+        let state = fields[0]
+            .parse()
+            .map_err(|e| format!("line {}: {}", n + 1, e))?;
+        let player = match fields[1] {
+            "+" => 1,
+            "-" => -1,
+            p => return Err(format!("line {}: invalid player {}", n + 1, p)),
+        };
+        let best = match fields.get(2) {
+            Some(m) => {
+                Some(state::parse_move(m).ok_or(format!("line {}: invalid move {}", n + 1, m))?)
+            }
+            None => None,
+        };
+        r.push(Position {
+            state,
+            player,
+            best,
+        });
+    }
+    Ok(r)
+}
+
+pub fn suite() -> Vec<Position> {
+    parse(SUITE).expect("invalid bench suite")
+}
+
+// searches every position of the suite and reports nodes, speed, depth and best move
+// returns the number of positions whose best move changed
+pub fn run(positions: &[Position], depth: u32) -> usize {
+    let mut total_nodes = 0;
+    let mut total_time = 0.0;
+    let mut changed = 0;
+
+    for (i, p) in positions.iter().enumerate() {
+        let mut search = Search::new();
+
+        let t0 = time::precise_time_s();
+        let result = search.think(&p.state, p.player, depth);
+        let t1 = time::precise_time_s();
+
+        total_nodes += search.nodes;
+        total_time += t1 - t0;
+
+        let (col, value) = match result {
+            Some(r) => r,
+            None => {
+                println!("{:2} no legal move", i);
+                continue;
+            }
+        };
+        let check = match p.best {
+            Some(best) if best != col => {
+                changed += 1;
+                format!(" changed, was {}", state::format_move(best))
+            }
+            _ => String::new(),
+        };
+        println!(
+            "{:2} depth {:2} best {} {:16} {:9} nodes {:9.0} nodes/s {:.3} seconds{}",
+            i,
+            search.depth,
+            state::format_move(col),
+            search::describe(value),
+            search.nodes,
+            search.nodes as f64 / (t1 - t0).max(1e-9),
+            t1 - t0,
+            check
+        );
+    }
+
+    println!(
+        "total {} nodes {:.0} nodes/s {:.3} seconds, {} best moves changed",
+        total_nodes,
+        total_nodes as f64 / total_time.max(1e-9),
+        total_time,
+        changed
+    );
+    changed
+}
+
+// node counts and best moves of two search configurations on the suite
+// returns the number of positions where their best moves differ
+pub fn compare(
+    positions: &[Position],
+    names: [&str; 2],
+    configs: [fn(&mut Search); 2],
+    depth: u32,
+) -> usize {
+    let mut total = [0u64; 2];
+    let mut differ = 0;
+    for (p, position) in positions.iter().enumerate() {
+        let mut nodes = [0u64; 2];
+        let mut best = [None; 2];
+        for i in 0..2 {
+            let mut search = Search::new();
+            configs[i](&mut search);
+            best[i] = search.think(&position.state, position.player, depth);
+            nodes[i] = search.nodes;
+            total[i] += search.nodes;
+        }
+        let check = match (best[0], best[1]) {
+            (Some((a, va)), Some((b, vb))) if a != b => {
+                differ += 1;
+                format!(
+                    " best {} {} against {} {}",
+                    state::format_move(a),
+                    search::describe(va),
+                    state::format_move(b),
+                    search::describe(vb)
+                )
+            }
+            _ => String::new(),
+        };
+        println!(
+            "{:2} {} {} {} {} ({:.1}%){}",
+            p,
+            names[0],
+            nodes[0],
+            names[1],
+            nodes[1],
+            100.0 * nodes[1] as f64 / nodes[0] as f64,
+            check
+        );
+    }
+    println!(
+        "total {} {} {} {} ({:.1}%), {} best moves differ",
+        names[0],
+        total[0],
+        names[1],
+        total[1],
+        100.0 * total[1] as f64 / total[0] as f64,
+        differ
+    );
+    differ
+}
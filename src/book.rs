@@ -0,0 +1,116 @@
+use state;
+use state::State;
+use std;
+use std::collections::HashSet;
+use std::io::Write;
+
+// Losing moves learned from lost games
+//
+// Kept in a text file, one move per line: the State encoding of the canonical position
+// with the player to move as +, then the move in that position. `#` starts a comment.
+// The search never plays a move of the book unless every other move is in it too.
+
+// the book of the terminal and the OpenGL window without `--book`, the other front
+// ends only use one when it is given
+pub const BOOK: &str = "book.txt";
+
+#[derive(Clone)]
+pub struct Book {
+    path: Option<String>,
+    losing: HashSet<(State, usize)>,
+}
+
+// canonical position with the player to move as +1, and `col` in it
+fn key(x: &State, player: i32, col: usize) -> (State, usize) {
+    let (x, id) = state::canonical(x, player);
+    (x, state::canonical_column(id, col))
+}
+
+impl Default for Book {
+    fn default() -> Book {
+        Book::new()
+    }
+}
+
+impl Book {
+    // an empty book that is not saved
+    pub fn new() -> Book {
+        Book {
+            path: None,
+            losing: HashSet::new(),
+        }
+    }
+
+    // the book saved in `path`, empty if the file does not exist yet
+    pub fn load(path: &str) -> Result<Book, String> {
+        let mut book = Book::new();
+        book.path = Some(path.to_string());
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(book),
+            Err(e) => return Err(format!("{}: {}", path, e)),
+        };
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 2 {
+                return Err(format!(
+                    "{}:{}: expected a position and a move",
+                    path,
+                    n + 1
+                ));
+            }
+            let x: State = fields[0]
+                .parse()
+                .map_err(|e| format!("{}:{}: {}", path, n + 1, e))?;
+            let col = state::parse_move(fields[1]).ok_or(format!(
+                "{}:{}: invalid move {}",
+                path,
+                n + 1,
+                fields[1]
+            ))?;
+            book.losing.insert(key(&x, 1, col));
+        }
+        Ok(book)
+    }
+
+    // writes the book back to the file it was loaded from
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let mut lines: Vec<String> = self
+            .losing
+            .iter()
+            .map(|&(ref x, col)| format!("{} {}", x.encode(), state::format_move(col)))
+            .collect();
+        lines.sort();
+        let mut f = std::fs::File::create(path)?;
+        writeln!(f, "# losing moves learned from lost games")?;
+        for line in lines.iter() {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.losing.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.losing.is_empty()
+    }
+
+    pub fn is_losing(&self, x: &State, player: i32, col: usize) -> bool {
+        self.losing.contains(&key(x, player, col))
+    }
+
+    // returns false if the move was already known
+    pub fn mark(&mut self, x: &State, player: i32, col: usize) -> bool {
+        self.losing.insert(key(x, player, col))
+    }
+}
@@ -0,0 +1,300 @@
+use negamax::GameState;
+use search;
+use search::Search;
+use state::State;
+use std;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use time;
+
+// C interface of the library, declared in include/connect4x4x4.h
+//
+// A game is created by c4_game_new and freed by c4_game_free, every other function
+// takes it as first argument. Columns are x + 4 * y with x and y from 0 to 3, cells
+// hold 1 or -1 for the players and 0 when empty. The functions returning an int
+// give -1 for invalid arguments, null pointers included. They are unsafe for Rust as
+// they trust the pointers they are given.
+//
+// The header is generated by cbindgen with cbindgen.toml, the `///` comments of the
+// functions go into it.
+
+const API_VERSION: c_int = 1;
+
+pub struct Game {
+    x: State,
+    player: i32,
+    history: Vec<(State, i32)>,
+    search: Search,
+}
+
+/// the version of the interface, it changes when a function changes
+#[no_mangle]
+pub extern "C" fn c4_api_version() -> c_int {
+    API_VERSION
+}
+
+/// empty board, player 1 to move
+#[no_mangle]
+pub extern "C" fn c4_game_new() -> *mut Game {
+    Box::into_raw(Box::new(Game {
+        x: State::new(),
+        player: 1,
+        history: Vec::new(),
+        search: Search::new(),
+    }))
+}
+
+/// frees a game of c4_game_new
+///
+/// # Safety
+///
+/// `game` must be null or come from c4_game_new and not be freed yet, it cannot be
+/// used once freed.
+#[no_mangle]
+pub unsafe extern "C" fn c4_game_free(game: *mut Game) {
+    if !game.is_null() {
+        drop(Box::from_raw(game));
+    }
+}
+
+unsafe fn with<F: FnOnce(&mut Game) -> c_int>(game: *mut Game, f: F) -> c_int {
+    match game.as_mut() {
+        Some(game) => f(game),
+        None => -1,
+    }
+}
+
+unsafe fn string<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+fn playable(game: &Game, col: c_int) -> bool {
+    let over = game.x.win(1) || game.x.win(-1);
+    (0..16).contains(&col) && !over && game.x.playable(col as usize)
+}
+
+/// options of the command line as one string, for example "--personality aggressive",
+/// 0 when they are accepted, -1 for an unknown option
+///
+/// # Safety
+///
+/// `game` must be null or come from c4_game_new and not be freed yet.
+/// `options` must be null or a string ending with 0.
+#[no_mangle]
+pub unsafe extern "C" fn c4_game_configure(game: *mut Game, options: *const c_char) -> c_int {
+    with(game, |game| {
+        let options = match string(options) {
+            Some(o) => o,
+            None => return -1,
+        };
+        match game.search.configure_options(options, &[]) {
+            Ok(_) => 0,
+            Err(_) => -1,
+        }
+    })
+}
+
+/// back to the empty board with player 1 to move
+///
+/// # Safety
+///
+/// `game` must be null or come from c4_game_new and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn c4_game_reset(game: *mut Game) -> c_int {
+    with(game, |game| {
+        game.x = State::new();
+        game.player = 1;
+        game.history.clear();
+        game.search.clear();
+        0
+    })
+}
+
+/// the position from its encoding, the 16 columns x + 4 * y separated by '/', each
+/// of 4 cells from bottom to top among '+', '-' and '.', and the player to move
+///
+/// # Safety
+///
+/// `game` must be null or come from c4_game_new and not be freed yet.
+/// `encoding` must be null or a string ending with 0.
+#[no_mangle]
+pub unsafe extern "C" fn c4_game_set(
+    game: *mut Game,
+    encoding: *const c_char,
+    player: c_int,
+) -> c_int {
+    with(game, |game| {
+        let x = match string(encoding).map(str::parse) {
+            Some(Ok(x)) => x,
+            _ => return -1,
+        };
+        if player != 1 && player != -1 {
+            return -1;
+        }
+        game.x = x;
+        game.player = player;
+        game.history.clear();
+        0
+    })
+}
+
+/// writes the encoding of the position and a final 0 into `buffer` of `size` bytes,
+/// returns the length of the encoding, nothing is written when it does not fit
+///
+/// # Safety
+///
+/// `game` must be null or come from c4_game_new and not be freed yet.
+/// `buffer` must be null or hold `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn c4_game_encode(
+    game: *mut Game,
+    buffer: *mut c_char,
+    size: usize,
+) -> c_int {
+    with(game, |game| {
+        let s = game.x.encode();
+        if !buffer.is_null() && s.len() < size {
+            std::ptr::copy_nonoverlapping(s.as_ptr() as *const c_char, buffer, s.len());
+            *buffer.add(s.len()) = 0;
+        }
+        s.len() as c_int
+    })
+}
+
+/// the player to move, 1 or -1
+///
+/// # Safety
+///
+/// `game` must be null or come from c4_game_new and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn c4_game_player(game: *mut Game) -> c_int {
+    with(game, |game| game.player)
+}
+
+/// the cell, 0 out of the board
+///
+/// # Safety
+///
+/// `game` must be null or come from c4_game_new and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn c4_game_get(game: *mut Game, x: c_int, y: c_int, z: c_int) -> c_int {
+    with(game, |game| {
+        if [x, y, z].iter().any(|c| !(0..4).contains(c)) {
+            return 0;
+        }
+        game.x.get(x as usize, y as usize, z as usize)
+    })
+}
+
+/// 1 when the player to move can play in the column
+///
+/// # Safety
+///
+/// `game` must be null or come from c4_game_new and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn c4_game_playable(game: *mut Game, col: c_int) -> c_int {
+    with(game, |game| playable(game, col) as c_int)
+}
+
+/// plays in the column for the player to move, 1 when played, 0 when illegal
+///
+/// # Safety
+///
+/// `game` must be null or come from c4_game_new and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn c4_game_play(game: *mut Game, col: c_int) -> c_int {
+    with(game, |game| {
+        if !playable(game, col) {
+            return 0;
+        }
+        game.history.push((game.x.clone(), game.player));
+        game.x.play(col as usize, game.player);
+        game.player = -game.player;
+        1
+    })
+}
+
+/// takes back the last move, 0 when there is none
+///
+/// # Safety
+///
+/// `game` must be null or come from c4_game_new and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn c4_game_undo(game: *mut Game) -> c_int {
+    with(game, |game| match game.history.pop() {
+        Some((x, player)) => {
+            game.x = x;
+            game.player = player;
+            1
+        }
+        None => 0,
+    })
+}
+
+/// 1 when `player` has a line of four
+///
+/// # Safety
+///
+/// `game` must be null or come from c4_game_new and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn c4_game_win(game: *mut Game, player: c_int) -> c_int {
+    with(game, |game| match player {
+        1 | -1 => game.x.win(player) as c_int,
+        _ => -1,
+    })
+}
+
+/// 1 when the board is full
+///
+/// # Safety
+///
+/// `game` must be null or come from c4_game_new and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn c4_game_full(game: *mut Game) -> c_int {
+    with(game, |game| game.x.full() as c_int)
+}
+
+/// best column for the player to move, searched until `depth` or for `movetime`
+/// milliseconds. A depth of 0 searches as deep as the movetime allows, or to depth 6
+/// when the movetime is 0 too. The value in its perspective is written to `value`
+/// when not null. -1 when the game is over.
+///
+/// # Safety
+///
+/// `game` must be null or come from c4_game_new and not be freed yet.
+/// `value` must be null or point to an int.
+#[no_mangle]
+pub unsafe extern "C" fn c4_search(
+    game: *mut Game,
+    depth: c_int,
+    movetime: c_int,
+    value: *mut c_int,
+) -> c_int {
+    with(game, |game| {
+        if game.x.win(1) || game.x.win(-1) || depth < 0 || movetime < 0 {
+            return -1;
+        }
+        let depth = match (depth, movetime) {
+            (0, 0) => search::DEPTH,
+            (0, _) => game.x.empty() as u32,
+            (d, _) => d as u32,
+        };
+        if movetime > 0 {
+            game.search.deadline = Some(time::precise_time_s() + movetime as f64 / 1000.0);
+        }
+        let best = game.search.think(&game.x, game.player, depth);
+        game.search.deadline = None;
+        match best {
+            Some((col, v)) => {
+                if let Some(value) = value.as_mut() {
+                    *value = v;
+                }
+                col as c_int
+            }
+            None => -1,
+        }
+    })
+}
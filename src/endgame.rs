@@ -0,0 +1,229 @@
+use negamax::GameState;
+use personality::Personality;
+use search;
+use search::WIN;
+use state;
+use state::State;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+// Exact solver for positions with few empty cells
+//
+// Searches until the end of the game, the values follow the convention of the search:
+// WIN - n for a win in n plies, -(WIN - n) for a loss and the draw value of the
+// personality for a draw, 0 when balanced.
+
+#[derive(Clone, Copy, PartialEq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+pub struct Solver {
+    // keyed like the search, by the position and whether the engine is to move
+    table: HashMap<(State, bool), (i32, Bound, usize)>,
+    personality: Personality,
+    // the player of the engine, whose personality values the draws
+    side: i32,
+    pub nodes: u64,
+}
+
+impl Default for Solver {
+    fn default() -> Solver {
+        Solver::new()
+    }
+}
+
+impl Solver {
+    pub fn new() -> Solver {
+        Solver::with_personality(Personality::balanced(), 1)
+    }
+
+    // draws valued by the personality of the engine playing `side`
+    pub fn with_personality(personality: Personality, side: i32) -> Solver {
+        Solver {
+            table: HashMap::new(),
+            personality,
+            side,
+            nodes: 0,
+        }
+    }
+
+    // the optimal column and the exact value in the perspective of `player`
+    pub fn solve(&mut self, x: &State, player: i32) -> Option<(usize, i32)> {
+        let mut alpha = -i32::MAX;
+        let mut best = None;
+        let moves = self.order(x, player, None);
+        for class in x.classes(&moves) {
+            let col = class[0];
+            let mut y = x.clone();
+            y.play(col, player);
+            let v = -self.negamax(&y, -player, -i32::MAX, -alpha, 1);
+            if best.is_none() || v > alpha {
+                alpha = v;
+                best = Some((col, v));
+            }
+        }
+        best
+    }
+
+    // every legal column with its exact value in the perspective of `player`, best first
+    pub fn analyse(&mut self, x: &State, player: i32) -> Vec<(usize, i32)> {
+        let moves: Vec<usize> = (0..16).filter(|&col| x.playable(col)).collect();
+        let mut r = Vec::new();
+        for class in x.classes(&moves) {
+            let mut y = x.clone();
+            y.play(class[0], player);
+            let v = -self.negamax(&y, -player, -i32::MAX, i32::MAX, 1);
+            for col in class {
+                r.push((col, v));
+            }
+        }
+        r.sort_by_key(|&(_, v)| Reverse(v));
+        r
+    }
+
+    fn negamax(&mut self, x: &State, player: i32, mut alpha: i32, mut beta: i32, ply: usize) -> i32 {
+        self.nodes += 1;
+
+        if x.win(-player) {
+            return -(WIN - ply as i32);
+        }
+        if x.full() {
+            return self.personality.draw(player, self.side);
+        }
+
+        alpha = alpha.max(-(WIN - ply as i32));
+        beta = beta.min(WIN - ply as i32 - 1);
+        if alpha >= beta {
+            return alpha;
+        }
+
+        let alpha0 = alpha;
+        let (k, id) = state::canonical(x, player);
+        let k = (k, player == self.side);
+        let mut tt_move = None;
+        if let Some(&(value, bound, best)) = self.table.get(&k) {
+            let value = search::from_table(value, ply);
+            match bound {
+                Bound::Exact => return value,
+                Bound::Lower if value >= beta => return value,
+                Bound::Upper if value <= alpha => return value,
+                _ => (),
+            }
+            tt_move = Some(state::symmetry_column(id, best));
+        }
+
+        let mut best_value = -i32::MAX;
+        let mut best_move = 0;
+        for col in self.order(x, player, tt_move) {
+            let mut y = x.clone();
+            y.play(col, player);
+            let v = -self.negamax(&y, -player, -beta, -alpha, ply + 1);
+            if v > best_value {
+                best_value = v;
+                best_move = col;
+            }
+            if v > alpha {
+                alpha = v;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_value <= alpha0 {
+            Bound::Upper
+        } else if best_value >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        let best = state::canonical_column(id, best_move);
+        self.table
+            .insert(k, (search::to_table(best_value, ply), bound, best));
+
+        best_value
+    }
+
+    // table move, then the wins, then the blocks, then centre first
+    fn order(&self, x: &State, player: i32, tt_move: Option<usize>) -> Vec<usize> {
+        let mut scored = Vec::new();
+        for (rank, &col) in search::STATIC_ORDER.iter().enumerate() {
+            let mut y = x.clone();
+            if !y.play(col, player) {
+                continue;
+            }
+            let class = if tt_move == Some(col) {
+                3
+            } else if y.win(player) {
+                2
+            } else {
+                let mut z = x.clone();
+                z.play(col, -player);
+                if z.win(-player) {
+                    1
+                } else {
+                    0
+                }
+            };
+            scored.push(((class, 16 - rank), col));
+        }
+        scored.sort_by_key(|&(key, _)| Reverse(key));
+        scored.into_iter().map(|(_, col)| col).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rng::Rng;
+    use search::Search;
+
+    // a position of a random game with `empty` empty cells and nobody winning yet,
+    // with the player to move
+    fn random_position(rng: &mut Rng, empty: usize) -> (State, i32) {
+        'game: loop {
+            let mut x = State::new();
+            let mut player = 1;
+            while x.empty() > empty {
+                let moves: Vec<usize> = (0..16).filter(|&col| x.playable(col)).collect();
+                x.play(moves[rng.below(moves.len())], player);
+                if x.win(player) {
+                    continue 'game;
+                }
+                player = -player;
+            }
+            return (x, player);
+        }
+    }
+
+    // the search without the solver nor the reductions, exact once deep enough
+    fn full_search() -> Search {
+        let mut search = Search::new();
+        search.endgame = 0;
+        search.lmr = 0;
+        search.futility = 0;
+        search
+    }
+
+    #[test]
+    fn agrees_with_the_search() {
+        let mut rng = Rng::new(1);
+        for _ in 0..20 {
+            let (x, player) = random_position(&mut rng, 12);
+            let solved = Solver::new().solve(&x, player).map(|(_, v)| v);
+            let searched = full_search()
+                .think(&x, player, x.empty() as u32)
+                .map(|(_, v)| v);
+            assert_eq!(solved, searched, "{}", x.encode());
+
+            let mut solved = Solver::new().analyse(&x, player);
+            let mut searched = full_search().analyse(&x, player, x.empty() as u32);
+            solved.sort();
+            searched.sort();
+            assert_eq!(solved, searched, "{}", x.encode());
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use negamax::GameState;
+use rng::Rng;
+use state;
+use state::State;
+
+// Environment for reinforcement learning, in the style of Gym
+//
+// An episode is a game from the empty board where the agent plays both sides, each step
+// is a move of the player to move in the column `action`, x + 4 * y. The observation is
+// the position seen by the player to move: the 64 cells holding their pieces (1 or 0),
+// the 64 cells holding the pieces of the opponent, then 1 when the player to move is
+// +1 and 0 otherwise, cells in the order x + 4 * y + 16 * z.
+//
+// The reward goes to the player who just moved: 1 for a win and 0 otherwise. A move in
+// a full column is illegal, it ends the episode with the reward ILLEGAL.
+//
+// With augmentation each episode is seen under one of the 8 symmetries of the board,
+// drawn at reset: the observations, the actions and the mask are those of the image of
+// the game, so that the agent sees all the symmetric games.
+
+pub const ACTIONS: usize = 16;
+pub const OBSERVATION: usize = 2 * 64 + 1;
+pub const ILLEGAL: f32 = -1.0;
+
+// the position seen by `player`
+pub fn observation(x: &State, player: i32) -> Vec<f32> {
+    let mut o = vec![0.0; OBSERVATION];
+    for (i, &c) in x.cells().iter().enumerate() {
+        if c == player {
+            o[i] = 1.0;
+        } else if c == -player {
+            o[64 + i] = 1.0;
+        }
+    }
+    if player == 1 {
+        o[128] = 1.0;
+    }
+    o
+}
+
+// the observation of x.symmetry(id) from the observation of x, to augment recorded
+// transitions; the actions follow with state::symmetry_column
+pub fn augment(observation: &[f32], id: usize) -> Vec<f32> {
+    let mut o = observation.to_vec();
+    for plane in 0..8 {
+        for i in 0..16 {
+            o[16 * plane + i] = observation[16 * plane + state::symmetry_column(id, i)];
+        }
+    }
+    o
+}
+
+pub struct Step {
+    pub observation: Vec<f32>,
+    pub reward: f32,
+    pub done: bool,
+    pub illegal: bool,
+}
+
+pub struct Env {
+    state: State,
+    player: i32,
+    done: bool,
+    // id of the symmetry of the episode
+    symmetry: usize,
+    // draws the symmetries, none without augmentation
+    rng: Option<Rng>,
+}
+
+impl Default for Env {
+    fn default() -> Env {
+        Env::new()
+    }
+}
+
+impl Env {
+    pub fn new() -> Env {
+        Env {
+            state: State::new(),
+            player: 1,
+            done: false,
+            symmetry: 0,
+            rng: None,
+        }
+    }
+
+    // each episode under a random symmetry
+    pub fn augmented(seed: u64) -> Env {
+        let mut env = Env::new();
+        env.rng = Some(Rng::new(seed));
+        env.reset();
+        env
+    }
+
+    pub fn reset(&mut self) -> Vec<f32> {
+        self.state = State::new();
+        self.player = 1;
+        self.done = false;
+        if let Some(ref mut rng) = self.rng {
+            self.symmetry = rng.below(8);
+        }
+        self.observation()
+    }
+
+    // the position as the agent sees it, under the symmetry of the episode
+    pub fn state(&self) -> State {
+        self.state.symmetry(self.symmetry)
+    }
+
+    pub fn player(&self) -> i32 {
+        self.player
+    }
+
+    pub fn done(&self) -> bool {
+        self.done
+    }
+
+    pub fn observation(&self) -> Vec<f32> {
+        observation(&self.state(), self.player)
+    }
+
+    // the legal actions, none once the episode is over
+    pub fn legal_action_mask(&self) -> [bool; ACTIONS] {
+        let mut mask = [false; ACTIONS];
+        if !self.done {
+            for (action, m) in mask.iter_mut().enumerate() {
+                *m = self
+                    .state
+                    .playable(state::symmetry_column(self.symmetry, action));
+            }
+        }
+        mask
+    }
+
+    pub fn step(&mut self, action: usize) -> Result<Step, String> {
+        if self.done {
+            return Err("the episode is over, reset the environment".to_string());
+        }
+        if action >= ACTIONS {
+            return Err(format!("action {} out of 0..{}", action, ACTIONS));
+        }
+        let col = state::symmetry_column(self.symmetry, action);
+        let mover = self.player;
+        if !self.state.play(col, mover) {
+            self.done = true;
+            return Ok(Step {
+                observation: self.observation(),
+                reward: ILLEGAL,
+                done: true,
+                illegal: true,
+            });
+        }
+        self.player = -mover;
+        let win = self.state.win(mover);
+        self.done = win || self.state.full();
+        Ok(Step {
+            observation: self.observation(),
+            reward: if win { 1.0 } else { 0.0 },
+            done: self.done,
+            illegal: false,
+        })
+    }
+}
+
+// the steps of a batch, the observations one after the other
+pub struct BatchStep {
+    pub observations: Vec<f32>,
+    pub rewards: Vec<f32>,
+    pub dones: Vec<bool>,
+    pub illegal: Vec<bool>,
+}
+
+// many environments stepped together; an episode that ends is reset at once, the
+// observation given for it is then the first one of the next episode
+pub struct Batch {
+    pub envs: Vec<Env>,
+}
+
+impl Batch {
+    pub fn new(n: usize) -> Batch {
+        Batch {
+            envs: (0..n).map(|_| Env::new()).collect(),
+        }
+    }
+
+    pub fn augmented(n: usize, seed: u64) -> Batch {
+        let mut rng = Rng::new(seed);
+        Batch {
+            envs: (0..n).map(|_| Env::augmented(rng.next_u64())).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    pub fn reset(&mut self) -> Vec<f32> {
+        self.envs.iter_mut().flat_map(|env| env.reset()).collect()
+    }
+
+    pub fn observations(&self) -> Vec<f32> {
+        self.envs.iter().flat_map(|env| env.observation()).collect()
+    }
+
+    // the masks one after the other
+    pub fn legal_action_mask(&self) -> Vec<bool> {
+        self.envs
+            .iter()
+            .flat_map(|env| env.legal_action_mask().to_vec())
+            .collect()
+    }
+
+    // one action per environment
+    pub fn step(&mut self, actions: &[usize]) -> Result<BatchStep, String> {
+        if actions.len() != self.envs.len() {
+            return Err(format!(
+                "{} actions for {} environments",
+                actions.len(),
+                self.envs.len()
+            ));
+        }
+        // checked before any environment moves
+        if let Some(&action) = actions.iter().find(|&&a| a >= ACTIONS) {
+            return Err(format!("action {} out of 0..{}", action, ACTIONS));
+        }
+        let mut r = BatchStep {
+            observations: Vec::with_capacity(self.envs.len() * OBSERVATION),
+            rewards: Vec::with_capacity(self.envs.len()),
+            dones: Vec::with_capacity(self.envs.len()),
+            illegal: Vec::with_capacity(self.envs.len()),
+        };
+        for (env, &action) in self.envs.iter_mut().zip(actions) {
+            let step = env.step(action)?;
+            let observation = if step.done {
+                env.reset()
+            } else {
+                step.observation
+            };
+            r.observations.extend(observation);
+            r.rewards.push(step.reward);
+            r.dones.push(step.done);
+            r.illegal.push(step.illegal);
+        }
+        Ok(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // under each symmetry the agent sees the game of its actions
+    #[test]
+    fn symmetry() {
+        let mut rng = Rng::new(7);
+        for id in 0..8 {
+            for _ in 0..20 {
+                let mut env = Env::new();
+                env.symmetry = id;
+                let mut real = State::new();
+                // the game played with the actions as columns
+                let mut image = State::new();
+                while !env.done() {
+                    let mask = env.legal_action_mask();
+                    for (action, &m) in mask.iter().enumerate() {
+                        assert_eq!(m, image.playable(action));
+                    }
+                    let actions: Vec<usize> = (0..ACTIONS).filter(|&a| mask[a]).collect();
+                    let action = actions[rng.below(actions.len())];
+                    let player = env.player();
+                    real.play(state::symmetry_column(id, action), player);
+                    image.play(action, player);
+                    let step = env.step(action).unwrap();
+                    assert!(!step.illegal);
+                    assert!(env.state() == image);
+                    assert!(real.symmetry(id) == image);
+                    assert_eq!(step.observation, observation(&image, -player));
+                    assert_eq!(augment(&observation(&real, -player), id), step.observation);
+                }
+            }
+        }
+    }
+}
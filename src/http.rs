@@ -0,0 +1,172 @@
+use json::Value;
+use std;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+// Just enough HTTP/1.1 for the local server: one request per connection,
+// the body read from Content-Length, no chunked encoding.
+
+// larger bodies are refused
+const MAX_BODY: usize = 1 << 20;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn read(stream: &TcpStream) -> Result<Request, String> {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).map_err(|e| e.to_string())?;
+        let mut words = line.split_whitespace();
+        let method = words.next().ok_or("empty request")?.to_string();
+        let target = words.next().ok_or("missing path")?;
+
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+                break;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(i) = line.find(':') {
+                let name = line[..i].trim().to_lowercase();
+                headers.push((name, line[i + 1..].trim().to_string()));
+            }
+        }
+
+        let length = match headers.iter().find(|h| h.0 == "content-length") {
+            Some(h) => h.1.parse().map_err(|_| "invalid Content-Length")?,
+            None => 0,
+        };
+        if length > MAX_BODY {
+            return Err("body too large".to_string());
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+        let body = String::from_utf8(body).map_err(|_| "body is not UTF-8")?;
+
+        let (path, query) = match target.find('?') {
+            Some(i) => (&target[..i], &target[i + 1..]),
+            None => (target, ""),
+        };
+        let query = query
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .map(|p| match p.find('=') {
+                Some(i) => (p[..i].to_string(), p[i + 1..].to_string()),
+                None => (p.to_string(), String::new()),
+            })
+            .collect();
+
+        Ok(Request {
+            method,
+            path: path.to_string(),
+            query,
+            headers,
+            body,
+        })
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.headers
+            .iter()
+            .find(|h| h.0 == name)
+            .map(|h| h.1.as_str())
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|p| p.0 == name)
+            .map(|p| p.1.as_str())
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    }
+}
+
+impl Response {
+    pub fn json(status: u16, value: &Value) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            body: format!("{}\n", value).into_bytes(),
+        }
+    }
+
+    pub fn html(body: &str) -> Response {
+        Response {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    pub fn error(status: u16, message: &str) -> Response {
+        let value = Value::Object(vec![
+            ("ok".to_string(), Value::Bool(false)),
+            ("error".to_string(), Value::String(message.to_string())),
+        ]);
+        Response::json(status, &value)
+    }
+
+    pub fn write_to(&self, mut stream: &TcpStream) -> std::io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+// Server-sent events: the headers, then events written as they come until the
+// connection is closed
+pub struct Events<'a> {
+    stream: &'a TcpStream,
+}
+
+impl<'a> Events<'a> {
+    pub fn start(mut stream: &'a TcpStream) -> std::io::Result<Events<'a>> {
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+             Connection: close\r\n\r\n"
+        )?;
+        stream.flush()?;
+        Ok(Events { stream })
+    }
+
+    pub fn send(&mut self, event: &str, data: &Value) -> std::io::Result<()> {
+        write!(self.stream, "event: {}\ndata: {}\n\n", event, data)?;
+        self.stream.flush()
+    }
+}
@@ -0,0 +1,431 @@
+use std;
+use std::fmt;
+
+// Minimal JSON values, enough for the line API and the server
+//
+// Objects keep the order of their keys, numbers are f64.
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    // the value of `key` in an object
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match *self {
+            Value::Object(ref fields) => fields.iter().find(|f| f.0 == key).map(|f| &f.1),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    // non negative integers only
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::Number(n) if n >= 0.0 && n.fract() == 0.0 => Some(n as u64),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match *self {
+            Value::Array(ref a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+// builds an object from its fields
+pub fn object(fields: Vec<(&str, Value)>) -> Value {
+    Value::Object(
+        fields
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+    )
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(n: i32) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(n: u32) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(n: u64) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::Number(n)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(o: Option<T>) -> Value {
+        o.map_or(Value::Null, |v| v.into())
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(v: Vec<T>) -> Value {
+        Value::Array(v.into_iter().map(|x| x.into()).collect())
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// compact, on one line
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) if n.is_finite() => write!(f, "{}", n),
+            Value::Number(_) => write!(f, "null"),
+            Value::String(ref s) => write_string(f, s),
+            Value::Array(ref a) => {
+                write!(f, "[")?;
+                for (i, v) in a.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(ref fields) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+// arrays and objects inside each other, the parser recurses for each of them
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), String> {
+        for c in word.chars() {
+            if self.chars.next() != Some(c) {
+                return Err(format!("expected {}", word));
+            }
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.chars.peek().cloned() {
+            Some('n') => self.expect("null").map(|_| Value::Null),
+            Some('t') => self.expect("true").map(|_| Value::Bool(true)),
+            Some('f') => self.expect("false").map(|_| Value::Bool(false)),
+            Some('"') => self.string().map(Value::String),
+            Some('[') => self.nested(Parser::array),
+            Some('{') => self.nested(Parser::object),
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let mut s = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if c.is_ascii_digit() || "+-.eE".contains(c) {
+                        s.push(c);
+                        self.chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                s.parse()
+                    .map(Value::Number)
+                    .map_err(|_| format!("invalid number {}", s))
+            }
+            Some(c) => Err(format!("unexpected {}", c)),
+            None => Err("unexpected end".to_string()),
+        }
+    }
+
+    fn nested<F>(&mut self, f: F) -> Result<Value, String>
+    where
+        F: FnOnce(&mut Parser<'a>) -> Result<Value, String>,
+    {
+        if self.depth == MAX_DEPTH {
+            return Err("too deeply nested".to_string());
+        }
+        self.depth += 1;
+        let r = f(self);
+        self.depth -= 1;
+        r
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.chars.next();
+        let mut a = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&']') {
+            self.chars.next();
+            return Ok(Value::Array(a));
+        }
+        loop {
+            a.push(self.value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => (),
+                Some(']') => return Ok(Value::Array(a)),
+                _ => return Err("expected , or ] in an array".to_string()),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.chars.next();
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+            return Ok(Value::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.chars.peek() != Some(&'"') {
+                return Err("expected a key in an object".to_string());
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if self.chars.next() != Some(':') {
+                return Err("expected : after a key".to_string());
+            }
+            fields.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => (),
+                Some('}') => return Ok(Value::Object(fields)),
+                _ => return Err("expected , or } in an object".to_string()),
+            }
+        }
+    }
+
+    // the 4 hexadecimal digits after \u
+    fn hex(&mut self) -> Result<u32, String> {
+        let hex: String = self.chars.by_ref().take(4).collect();
+        match u32::from_str_radix(&hex, 16) {
+            Ok(n) if hex.len() == 4 => Ok(n),
+            _ => Err(format!("invalid escape \\u{}", hex)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.chars.next();
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => {
+                        let mut n = self.hex()?;
+                        // outside the basic plane, a pair of surrogates
+                        if (0xd800..0xdc00).contains(&n) {
+                            if self.chars.next() != Some('\\') || self.chars.next() != Some('u') {
+                                return Err("unpaired surrogate".to_string());
+                            }
+                            let low = self.hex()?;
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return Err("unpaired surrogate".to_string());
+                            }
+                            n = 0x10000 + ((n - 0xd800) << 10) + (low - 0xdc00);
+                        }
+                        let c = std::char::from_u32(n).ok_or("unpaired surrogate")?;
+                        s.push(c);
+                    }
+                    _ => return Err("invalid escape".to_string()),
+                },
+                Some(c) => s.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+        depth: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    match parser.chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected {} after the value", c)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        let v = parse(r#" {"a": [1, -2.5e1, true, null], "b": {}, "c": []} "#).unwrap();
+        assert_eq!(
+            v,
+            Value::Object(vec![
+                (
+                    "a".to_string(),
+                    Value::Array(vec![
+                        Value::Number(1.0),
+                        Value::Number(-25.0),
+                        Value::Bool(true),
+                        Value::Null,
+                    ])
+                ),
+                ("b".to_string(), Value::Object(vec![])),
+                ("c".to_string(), Value::Array(vec![])),
+            ])
+        );
+        assert_eq!(v.to_string(), r#"{"a":[1,-25,true,null],"b":{},"c":[]}"#);
+        assert_eq!(parse(&v.to_string()), Ok(v));
+    }
+
+    #[test]
+    fn errors() {
+        for text in [
+            "",
+            "[1,]",
+            "[1 2]",
+            "{1:2}",
+            r#"{"a" 1}"#,
+            r#"{"a":1"#,
+            "tru",
+            "1 2",
+            r#""ab"#,
+        ]
+        .iter()
+        {
+            assert!(parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn nesting() {
+        let ok = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+        assert!(parse(&ok).is_ok());
+        let deep = "[".repeat(MAX_DEPTH + 1) + &"]".repeat(MAX_DEPTH + 1);
+        assert_eq!(parse(&deep), Err("too deeply nested".to_string()));
+        let attack = "[".repeat(200000) + &"]".repeat(200000);
+        assert!(parse(&attack).is_err());
+        let objects = r#"{"a":"#.repeat(MAX_DEPTH + 1) + "1" + &"}".repeat(MAX_DEPTH + 1);
+        assert_eq!(parse(&objects), Err("too deeply nested".to_string()));
+    }
+
+    #[test]
+    fn escapes() {
+        let v = parse(r#""\"\\\/\b\f\n\r\té\u00e9\u0001""#).unwrap();
+        assert_eq!(
+            v,
+            Value::String("\"\\/\u{8}\u{c}\n\r\t\u{e9}\u{e9}\u{1}".to_string())
+        );
+        assert_eq!(parse(&v.to_string()), Ok(v));
+        assert!(parse(r#""\x""#).is_err());
+        assert!(parse(r#""\u12""#).is_err());
+    }
+
+    #[test]
+    fn surrogates() {
+        let v = parse(r#""\ud83d\ude00""#).unwrap();
+        assert_eq!(v, Value::String("\u{1f600}".to_string()));
+        assert_eq!(parse(&v.to_string()), Ok(v));
+        assert!(parse(r#""\ud83d""#).is_err());
+        assert!(parse(r#""\ud83dx""#).is_err());
+        assert!(parse(r#""\ud83d\u0041""#).is_err());
+        assert!(parse(r#""\ude00""#).is_err());
+    }
+}
@@ -0,0 +1,652 @@
+use negamax::GameState;
+use state;
+use state::State;
+use std;
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Human against human over TCP, one side hosts the game and the other joins it
+//
+// Both sides write text lines, the first one being the hello with the protocol version:
+// hello 1 NAME
+// move 23
+// undo                  asks to take back the last move
+// undo yes|no           the answer, both sides take it back on yes
+// resign
+// chat TEXT
+// sync STATE 22 41 ...  the position and the moves from the empty board
+// sync                  asks the host for a sync
+//
+// The host plays + and its game is the reference: it sends a sync after each hello and
+// when the other side finds a message that does not fit its game. A lost connection is
+// awaited again by the host and retried every second by the other side.
+
+pub const VERSION: u32 = 1;
+pub const PORT: u16 = 4445;
+// seconds given to the other side for its hello, a silent connection is then dropped
+const HELLO_TIMEOUT: u64 = 5;
+
+#[derive(Clone, PartialEq)]
+pub enum Message {
+    Hello(u32, String),
+    Move(usize),
+    Undo,
+    UndoAnswer(bool),
+    Resign,
+    Chat(String),
+    Sync(Box<State>, Vec<usize>),
+    SyncRequest,
+}
+
+impl Message {
+    pub fn parse(line: &str) -> Result<Message, String> {
+        let line = line.trim_end();
+        let (word, rest) = match line.find(' ') {
+            Some(i) => (&line[..i], &line[i + 1..]),
+            None => (line, ""),
+        };
+        match (word, rest) {
+            ("hello", _) => {
+                let mut words = rest.splitn(2, ' ');
+                let version = words
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .ok_or("hello needs a version")?;
+                Ok(Message::Hello(
+                    version,
+                    words.next().unwrap_or("").to_string(),
+                ))
+            }
+            ("move", m) => state::parse_move(m)
+                .map(Message::Move)
+                .ok_or(format!("invalid move {}", m)),
+            ("undo", "") => Ok(Message::Undo),
+            ("undo", "yes") => Ok(Message::UndoAnswer(true)),
+            ("undo", "no") => Ok(Message::UndoAnswer(false)),
+            ("resign", "") => Ok(Message::Resign),
+            ("chat", text) => Ok(Message::Chat(text.to_string())),
+            ("sync", "") => Ok(Message::SyncRequest),
+            ("sync", _) => {
+                let mut words = rest.split_whitespace();
+                let x = words.next().unwrap_or("").parse()?;
+                let moves = words
+                    .map(|m| state::parse_move(m).ok_or(format!("invalid move {}", m)))
+                    .collect::<Result<Vec<usize>, String>>()?;
+                Ok(Message::Sync(Box::new(x), moves))
+            }
+            _ => Err(format!("unknown message {}", line)),
+        }
+    }
+}
+
+// on one line, without the end of line
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Message::Hello(version, ref name) => write!(f, "hello {} {}", version, name),
+            Message::Move(col) => write!(f, "move {}", state::format_move(col)),
+            Message::Undo => write!(f, "undo"),
+            Message::UndoAnswer(yes) => write!(f, "undo {}", if yes { "yes" } else { "no" }),
+            Message::Resign => write!(f, "resign"),
+            Message::Chat(ref text) => write!(f, "chat {}", text.replace('\n', " ")),
+            Message::Sync(ref x, ref moves) => {
+                write!(f, "sync {}", x.encode())?;
+                for &col in moves.iter() {
+                    write!(f, " {}", state::format_move(col))?;
+                }
+                Ok(())
+            }
+            Message::SyncRequest => write!(f, "sync"),
+        }
+    }
+}
+
+pub enum Event {
+    // the name given in the hello of the other side
+    Connected(String),
+    Received(Message),
+    Lost(String),
+}
+
+// the connection to the other side, kept alive by a thread reconnecting after a loss
+pub struct Peer {
+    stream: Arc<Mutex<Option<TcpStream>>>,
+    events: Receiver<Event>,
+}
+
+impl Peer {
+    // waits for the other side on every interface, to be reachable on the LAN
+    pub fn host(port: u16, name: &str) -> Result<Peer, String> {
+        let listener =
+            TcpListener::bind(("0.0.0.0", port)).map_err(|e| format!("port {}: {}", port, e))?;
+        Ok(Peer::start(name, move || {
+            listener.accept().map(|(s, _)| s).map_err(|e| e.to_string())
+        }))
+    }
+
+    // `address` is HOST or HOST:PORT
+    pub fn join(address: &str, name: &str) -> Peer {
+        let address = if address.contains(':') {
+            address.to_string()
+        } else {
+            format!("{}:{}", address, PORT)
+        };
+        let mut first = true;
+        Peer::start(name, move || {
+            if !first {
+                std::thread::sleep(Duration::from_secs(1));
+            }
+            first = false;
+            TcpStream::connect(address.as_str()).map_err(|e| format!("{}: {}", address, e))
+        })
+    }
+
+    fn start<F>(name: &str, connect: F) -> Peer
+    where
+        F: FnMut() -> Result<TcpStream, String> + Send + 'static,
+    {
+        let stream = Arc::new(Mutex::new(None));
+        let (sender, events) = channel();
+        let hello = Message::Hello(VERSION, name.to_string());
+        let shared = stream.clone();
+        std::thread::spawn(move || run(connect, &hello, &shared, &sender));
+        Peer { stream, events }
+    }
+
+    pub fn connected(&self) -> bool {
+        self.stream.lock().unwrap().is_some()
+    }
+
+    // false when there is no connection
+    pub fn send(&self, message: &Message) -> bool {
+        match *self.stream.lock().unwrap() {
+            Some(ref s) => {
+                let mut s: &TcpStream = s;
+                writeln!(s, "{}", message).is_ok()
+            }
+            None => false,
+        }
+    }
+
+    pub fn poll(&self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+}
+
+// connects, reads the messages until the connection is lost and starts again,
+// stops once the Peer is dropped
+fn run<F>(
+    mut connect: F,
+    hello: &Message,
+    shared: &Mutex<Option<TcpStream>>,
+    events: &Sender<Event>,
+) where
+    F: FnMut() -> Result<TcpStream, String>,
+{
+    let mut last_error = String::new();
+    loop {
+        let mut stream = match connect() {
+            Ok(stream) => stream,
+            Err(e) => {
+                // the side joining retries, only a new error is worth telling
+                if e != last_error && events.send(Event::Lost(e.clone())).is_err() {
+                    return;
+                }
+                last_error = e;
+                continue;
+            }
+        };
+        last_error.clear();
+
+        let mut reader = match stream.try_clone() {
+            Ok(s) => BufReader::new(s),
+            Err(_) => continue,
+        };
+        let timeout = Some(Duration::from_secs(HELLO_TIMEOUT));
+        if stream.set_read_timeout(timeout).is_err() || writeln!(stream, "{}", hello).is_err() {
+            continue;
+        }
+        let mut line = String::new();
+        let name = match reader.read_line(&mut line).map(|_| Message::parse(&line)) {
+            Ok(Ok(Message::Hello(VERSION, name))) => name,
+            Ok(Ok(Message::Hello(version, _))) => {
+                let e = format!("protocol version {} instead of {}", version, VERSION);
+                if events.send(Event::Lost(e)).is_err() {
+                    return;
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        // the game may wait long for a move once both sides are there
+        if stream.set_read_timeout(None).is_err() {
+            continue;
+        }
+
+        *shared.lock().unwrap() = Some(stream);
+        if events.send(Event::Connected(name)).is_err() {
+            return;
+        }
+        let mut error = "connection closed".to_string();
+        for line in reader.lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    error = e.to_string();
+                    break;
+                }
+            };
+            // a newer version may send more, the unknown lines are skipped
+            if let Ok(message) = Message::parse(&line) {
+                if events.send(Event::Received(message)).is_err() {
+                    return;
+                }
+            }
+        }
+        *shared.lock().unwrap() = None;
+        if events.send(Event::Lost(error)).is_err() {
+            return;
+        }
+    }
+}
+
+// the game kept by each side
+#[derive(Clone)]
+pub struct Game {
+    pub x: State,
+    // from the empty board, + plays first
+    pub moves: Vec<usize>,
+    // the local player, + for the host
+    pub side: i32,
+    pub resigned: Option<i32>,
+    // the undo asked by this side and by the other one
+    pub undo_sent: bool,
+    pub undo_received: bool,
+}
+
+impl Game {
+    pub fn new(side: i32) -> Game {
+        Game {
+            x: State::new(),
+            moves: Vec::new(),
+            side,
+            resigned: None,
+            undo_sent: false,
+            undo_received: false,
+        }
+    }
+
+    // the player to move
+    pub fn player(&self) -> i32 {
+        if self.moves.len().is_multiple_of(2) {
+            1
+        } else {
+            -1
+        }
+    }
+
+    pub fn winner(&self) -> Option<i32> {
+        if self.x.win(1) {
+            Some(1)
+        } else if self.x.win(-1) {
+            Some(-1)
+        } else {
+            self.resigned.map(|p| -p)
+        }
+    }
+
+    pub fn over(&self) -> bool {
+        self.winner().is_some() || self.x.full()
+    }
+
+    fn play(&mut self, col: usize) -> bool {
+        let player = self.player();
+        if self.over() || !self.x.play(col, player) {
+            return false;
+        }
+        self.moves.push(col);
+        self.undo_sent = false;
+        self.undo_received = false;
+        true
+    }
+
+    fn undo(&mut self) {
+        let mut moves = self.moves.clone();
+        moves.pop();
+        let x = replay(&moves).expect("moves played already");
+        self.x = x;
+        self.moves = moves;
+        self.resigned = None;
+    }
+
+    pub fn sync(&self) -> Message {
+        Message::Sync(Box::new(self.x.clone()), self.moves.clone())
+    }
+
+    // a message of the other side, an error means the games differ
+    fn receive(&mut self, message: &Message) -> Result<(), String> {
+        match *message {
+            Message::Move(col) => {
+                if self.player() == self.side {
+                    return Err("move out of turn".to_string());
+                }
+                if !self.play(col) {
+                    return Err(format!("illegal move {}", state::format_move(col)));
+                }
+            }
+            Message::Undo => {
+                if self.moves.is_empty() {
+                    return Err("nothing to undo".to_string());
+                }
+                self.undo_received = true;
+            }
+            Message::UndoAnswer(yes) => {
+                if !self.undo_sent {
+                    return Err("no undo asked".to_string());
+                }
+                self.undo_sent = false;
+                if yes {
+                    self.undo();
+                }
+            }
+            Message::Resign => self.resigned = Some(-self.side),
+            Message::Sync(ref x, ref moves) => {
+                if self.side == 1 {
+                    return Err("sync from the side joining".to_string());
+                }
+                if replay(moves)? != **x {
+                    return Err("the sync moves do not give its position".to_string());
+                }
+                self.x = (**x).clone();
+                self.moves = moves.clone();
+                self.resigned = None;
+                self.undo_sent = false;
+                self.undo_received = false;
+            }
+            Message::Hello(..) | Message::Chat(_) | Message::SyncRequest => (),
+        }
+        Ok(())
+    }
+}
+
+// the position after `moves` from the empty board
+fn replay(moves: &[usize]) -> Result<State, String> {
+    let mut x = State::new();
+    let mut player = 1;
+    for &col in moves.iter() {
+        if x.win(1) || x.win(-1) || !x.play(col, player) {
+            return Err(format!("illegal move {}", state::format_move(col)));
+        }
+        player = -player;
+    }
+    Ok(x)
+}
+
+// a Game played with a Peer, what the terminal and the OpenGL window use
+pub struct Remote {
+    pub game: Game,
+    peer: Peer,
+    // of the other side
+    pub name: String,
+}
+
+fn side_name(player: i32) -> &'static str {
+    if player == 1 {
+        "+"
+    } else {
+        "-"
+    }
+}
+
+impl Remote {
+    pub fn new(peer: Peer, host: bool) -> Remote {
+        Remote {
+            game: Game::new(if host { 1 } else { -1 }),
+            peer,
+            name: String::new(),
+        }
+    }
+
+    // --host [--port N] or --join HOST[:PORT], with --name NAME, None without them
+    pub fn from_args(args: &[String]) -> Result<Option<Remote>, String> {
+        let value = |flag: &str| args.iter().position(|a| a == flag).map(|i| args.get(i + 1));
+        let name = match value("--name") {
+            Some(n) => n.ok_or("--name needs a name")?.clone(),
+            None => std::env::var("USER").unwrap_or_else(|_| "anonymous".to_string()),
+        };
+        let port = match value("--port") {
+            Some(p) => p
+                .and_then(|p| p.parse().ok())
+                .ok_or("--port needs a number")?,
+            None => PORT,
+        };
+        if args.iter().any(|a| a == "--host") {
+            let peer = Peer::host(port, &name)?;
+            println!("hosting on port {}, waiting for the other player", port);
+            return Ok(Some(Remote::new(peer, true)));
+        }
+        match value("--join") {
+            Some(address) => {
+                let address = address.ok_or("--join needs an address")?;
+                println!("joining {}", address);
+                Ok(Some(Remote::new(Peer::join(address, &name), false)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn host(&self) -> bool {
+        self.game.side == 1
+    }
+
+    // the local player can move
+    pub fn our_turn(&self) -> bool {
+        self.peer.connected() && !self.game.over() && self.game.player() == self.game.side
+    }
+
+    fn send(&self, message: &Message) -> Result<(), String> {
+        if self.peer.send(message) {
+            Ok(())
+        } else {
+            Err("not connected".to_string())
+        }
+    }
+
+    pub fn play(&mut self, col: usize) -> Result<(), String> {
+        if !self.our_turn() {
+            return Err("not your turn".to_string());
+        }
+        let mut game = self.game.clone();
+        if !game.play(col) {
+            return Err(format!("illegal move {}", state::format_move(col)));
+        }
+        self.send(&Message::Move(col))?;
+        self.game = game;
+        Ok(())
+    }
+
+    // asks the other side to take back the last move
+    pub fn undo(&mut self) -> Result<(), String> {
+        if self.game.moves.is_empty() {
+            return Err("nothing to undo".to_string());
+        }
+        self.send(&Message::Undo)?;
+        self.game.undo_sent = true;
+        Ok(())
+    }
+
+    pub fn answer(&mut self, yes: bool) -> Result<(), String> {
+        if !self.game.undo_received {
+            return Err("no undo asked".to_string());
+        }
+        self.send(&Message::UndoAnswer(yes))?;
+        self.game.undo_received = false;
+        if yes {
+            self.game.undo();
+        }
+        Ok(())
+    }
+
+    pub fn resign(&mut self) -> Result<(), String> {
+        if self.game.over() {
+            return Err("the game is over".to_string());
+        }
+        self.send(&Message::Resign)?;
+        self.game.resigned = Some(self.game.side);
+        Ok(())
+    }
+
+    pub fn chat(&mut self, text: &str) -> Result<(), String> {
+        self.send(&Message::Chat(text.to_string()))
+    }
+
+    // handles what came from the other side, returns the lines to show
+    pub fn update(&mut self) -> Vec<String> {
+        let mut notes = Vec::new();
+        while let Some(event) = self.peer.poll() {
+            match event {
+                Event::Connected(name) => {
+                    notes.push(format!(
+                        "{} connected, you play {}",
+                        name,
+                        side_name(self.game.side)
+                    ));
+                    self.name = name;
+                    if self.host() {
+                        self.peer.send(&self.game.sync());
+                    }
+                }
+                Event::Lost(e) => notes.push(format!("connection lost: {}", e)),
+                Event::Received(message) => {
+                    match message {
+                        Message::Move(col) => {
+                            notes.push(format!("{} plays {}", self.name, state::format_move(col)))
+                        }
+                        Message::Undo => notes.push(format!(
+                            "{} asks to take back the last move, yes or no ?",
+                            self.name
+                        )),
+                        Message::UndoAnswer(yes) => notes.push(format!(
+                            "{} {} the undo",
+                            self.name,
+                            if yes { "accepts" } else { "refuses" }
+                        )),
+                        Message::Resign => notes.push(format!("{} resigns", self.name)),
+                        Message::Chat(ref text) => notes.push(format!("{}: {}", self.name, text)),
+                        Message::Sync(_, ref moves)
+                            if !self.host() && *moves != self.game.moves =>
+                        {
+                            notes.push(format!("game synchronised, {} moves", moves.len()))
+                        }
+                        Message::SyncRequest if self.host() => {
+                            self.peer.send(&self.game.sync());
+                        }
+                        _ => (),
+                    }
+                    if let Err(e) = self.game.receive(&message) {
+                        notes.push(format!("out of sync: {}", e));
+                        self.peer.send(&if self.host() {
+                            self.game.sync()
+                        } else {
+                            Message::SyncRequest
+                        });
+                    }
+                }
+            }
+        }
+        notes
+    }
+
+    // a line about the state of the game
+    pub fn status(&self) -> String {
+        let game = &self.game;
+        match game.winner() {
+            Some(p) if p == game.side => "you won".to_string(),
+            Some(_) => format!("{} won", self.name),
+            None if game.x.full() => "draw".to_string(),
+            None if !self.peer.connected() => "waiting for the connection".to_string(),
+            None if game.player() == game.side => "your move".to_string(),
+            None => format!("{} to move", self.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    // updates both sides until `done` holds, false after 10 seconds
+    fn wait<F>(host: &mut Remote, guest: &mut Remote, done: F) -> bool
+    where
+        F: Fn(&Remote, &Remote) -> bool,
+    {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(10) {
+            host.update();
+            guest.update();
+            if done(host, guest) {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn loopback() {
+        let mut host = Remote::new(Peer::host(4446, "host").unwrap(), true);
+        let mut guest = Remote::new(Peer::join("127.0.0.1:4446", "guest"), false);
+        assert!(wait(&mut host, &mut guest, |h, g| h.name == "guest"
+            && g.name == "host"));
+        assert!(host.our_turn() && !guest.our_turn());
+
+        host.play(5).unwrap();
+        assert!(wait(&mut host, &mut guest, |_, g| g.game.moves == [5]));
+        guest.play(10).unwrap();
+        assert!(wait(&mut host, &mut guest, |h, _| h.game.moves == [5, 10]));
+
+        guest.undo().unwrap();
+        assert!(wait(&mut host, &mut guest, |h, _| h.game.undo_received));
+        host.answer(true).unwrap();
+        assert!(wait(&mut host, &mut guest, |_, g| g.game.moves == [5]));
+        assert_eq!(host.game.moves, [5]);
+        assert!(!guest.game.undo_sent);
+
+        guest.resign().unwrap();
+        assert!(wait(&mut host, &mut guest, |h, _| h.game.over()));
+        assert_eq!(host.game.winner(), Some(1));
+        assert_eq!(guest.game.winner(), Some(1));
+        assert_eq!(host.status(), "you won");
+    }
+
+    #[test]
+    fn sync_from_the_side_joining() {
+        let mut x = State::new();
+        x.play(0, 1);
+        let mut host = Game::new(1);
+        let sync = Message::Sync(Box::new(x.clone()), vec![0]);
+        assert!(host.receive(&sync).is_err());
+        assert!(host.moves.is_empty());
+        let mut guest = Game::new(-1);
+        assert!(guest.receive(&Message::Sync(Box::new(x), vec![0])).is_ok());
+        assert_eq!(guest.moves, [0]);
+    }
+
+    #[test]
+    fn silent_connection() {
+        let _peer = Peer::host(4447, "host").unwrap();
+        let mut silent = TcpStream::connect("127.0.0.1:4447").unwrap();
+        // the host drops it after HELLO_TIMEOUT, ending the stream after its own hello
+        let mut lines = String::new();
+        silent
+            .set_read_timeout(Some(Duration::from_secs(20)))
+            .unwrap();
+        std::io::Read::read_to_string(&mut silent, &mut lines).unwrap();
+        assert_eq!(lines, "hello 1 host\n");
+    }
+}
@@ -0,0 +1,23 @@
+// re-exported for the crates built on this one, such as python/
+pub extern crate negamax;
+pub extern crate time;
+
+pub mod api;
+pub mod arena;
+pub mod bench;
+pub mod book;
+pub mod capi;
+pub mod endgame;
+pub mod env;
+pub mod http;
+pub mod json;
+pub mod lan;
+pub mod mcts;
+pub mod network;
+pub mod personality;
+pub mod rng;
+pub mod search;
+pub mod selfplay;
+pub mod state;
+pub mod tournament;
+pub mod trace;
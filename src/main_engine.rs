@@ -0,0 +1,227 @@
+extern crate connect4x4x4;
+extern crate negamax;
+extern crate time;
+
+use connect4x4x4::search::Search;
+use connect4x4x4::state::State;
+use connect4x4x4::{search, state};
+use negamax::GameState;
+use std::io::BufRead;
+use std::sync::atomic::Ordering;
+use std::thread::JoinHandle;
+
+// Line protocol modeled on UCI, for GUIs and scripts driving the engine as a subprocess
+//
+// uci                                   answers the engine id and uciok
+// isready                               answers readyok, also during a search
+// newgame                               forgets the previous searches
+// position [startpos] [moves 23 41 ...] the player + starts
+// position state STATE +|- [moves ...]  from a State encoding and the player to move
+// go [depth N] [movetime MS] [infinite] searches, writes info lines and bestmove
+// stop                                  ends the search, bestmove is still written
+// quit
+//
+// The moves are written xy as in the terminal. After each iteration the search writes
+// `info depth D score cp V|mate N nodes N time MS nps N pv ...`, the score being in the
+// perspective of the player to move and mate counted in its moves, negative when it loses.
+
+fn position(words: &[&str]) -> Result<(State, i32), String> {
+    let mut x = State::new();
+    let mut player = 1;
+    let mut i = 0;
+    match words.first() {
+        Some(&"startpos") => i = 1,
+        Some(&"state") => {
+            x = words
+                .get(1)
+                .ok_or("position state needs a position")?
+                .parse()?;
+            player = match words.get(2) {
+                Some(&"+") => 1,
+                Some(&"-") => -1,
+                _ => return Err("position state needs the player to move, + or -".to_string()),
+            };
+            i = 3;
+        }
+        _ => (),
+    }
+    match words.get(i) {
+        None => (),
+        Some(&"moves") => {
+            for m in words[i + 1..].iter() {
+                let col = state::parse_move(m).ok_or(format!("invalid move {}", m))?;
+                if x.win(1) || x.win(-1) || !x.play(col, player) {
+                    return Err(format!("illegal move {}", m));
+                }
+                player = -player;
+            }
+        }
+        Some(w) => return Err(format!("unexpected {}", w)),
+    }
+    Ok((x, player))
+}
+
+// depth and time limit in milliseconds
+fn limits(words: &[&str], x: &State) -> Result<(u32, Option<u64>), String> {
+    let mut depth = None;
+    let mut movetime = None;
+    let mut i = 0;
+    while i < words.len() {
+        match words[i] {
+            "depth" => {
+                depth = Some(
+                    words
+                        .get(i + 1)
+                        .and_then(|n| n.parse().ok())
+                        .ok_or("go depth needs a number")?,
+                );
+                i += 1;
+            }
+            "movetime" => {
+                movetime = Some(
+                    words
+                        .get(i + 1)
+                        .and_then(|n| n.parse().ok())
+                        .ok_or("go movetime needs milliseconds")?,
+                );
+                i += 1;
+            }
+            "infinite" => depth = Some(x.empty() as u32),
+            w => return Err(format!("unexpected {}", w)),
+        }
+        i += 1;
+    }
+    let depth = match (depth, movetime) {
+        (Some(depth), _) => depth,
+        (None, Some(_)) => x.empty() as u32,
+        (None, None) => search::DEPTH,
+    };
+    Ok((depth, movetime))
+}
+
+fn score(value: i32) -> String {
+    match search::mate_moves(value) {
+        Some(n) => format!("mate {}", n),
+        None => format!("cp {}", value),
+    }
+}
+
+fn go(search: &mut Search, x: &State, player: i32, depth: u32) {
+    if x.win(1) || x.win(-1) {
+        println!("bestmove none");
+        return;
+    }
+
+    let t0 = time::precise_time_s();
+    search.nodes = 0;
+    let best = search.iterate(x, player, depth, |search, col, value| {
+        let t = time::precise_time_s() - t0;
+        let pv: Vec<String> = search
+            .pv(x, player, col)
+            .into_iter()
+            .map(state::format_move)
+            .collect();
+        println!(
+            "info depth {} score {} nodes {} time {} nps {:.0} pv {}",
+            search.depth,
+            score(value),
+            search.nodes,
+            (1000.0 * t) as u64,
+            search.nodes as f64 / t.max(1e-9),
+            pv.join(" ")
+        );
+    });
+
+    match best {
+        Some((col, _)) => println!("bestmove {}", state::format_move(col)),
+        None => println!("bestmove none"),
+    }
+}
+
+// the search, once the running one is over
+fn idle<'a>(
+    search: &'a mut Option<Search>,
+    thread: &mut Option<JoinHandle<Search>>,
+) -> &'a mut Search {
+    if let Some(t) = thread.take() {
+        *search = Some(t.join().expect("search thread panicked"));
+    }
+    search.as_mut().expect("no search")
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut search = Search::new();
+    if let Err(e) = search.configure(&args) {
+        println!("{}", e);
+        return;
+    }
+    let stop = search.stop.clone();
+
+    let mut x = State::new();
+    let mut player = 1;
+    let mut search = Some(search);
+    let mut thread = None;
+
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, words) = match words.split_first() {
+            Some((&command, words)) => (command, words),
+            None => continue,
+        };
+
+        match command {
+            "uci" => {
+                println!("id name 4x4x4");
+                println!("id author Mario Geiger");
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "newgame" | "ucinewgame" => {
+                idle(&mut search, &mut thread).clear();
+                x = State::new();
+                player = 1;
+            }
+            "position" => match position(words) {
+                Ok(p) => {
+                    x = p.0;
+                    player = p.1;
+                }
+                Err(e) => println!("info string {}", e),
+            },
+            "go" => {
+                let (depth, movetime) = match limits(words, &x) {
+                    Ok(limits) => limits,
+                    Err(e) => {
+                        println!("info string {}", e);
+                        continue;
+                    }
+                };
+                idle(&mut search, &mut thread);
+                let mut s = search.take().expect("no search");
+                stop.store(false, Ordering::Relaxed);
+                s.deadline = movetime.map(|ms| time::precise_time_s() + ms as f64 / 1000.0);
+                let x = x.clone();
+                thread = Some(std::thread::spawn(move || {
+                    go(&mut s, &x, player, depth);
+                    s
+                }));
+            }
+            "stop" => stop.store(true, Ordering::Relaxed),
+            "quit" => {
+                stop.store(true, Ordering::Relaxed);
+                break;
+            }
+            c => println!("info string unknown command {}", c),
+        }
+    }
+
+    // the last search still writes its bestmove
+    idle(&mut search, &mut thread);
+}
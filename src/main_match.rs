@@ -0,0 +1,151 @@
+extern crate connect4x4x4;
+
+use connect4x4x4::arena::{option, Engine, Limits, Score, Sprt};
+use connect4x4x4::{arena, state};
+
+// Plays games between two engines and tells which one is stronger
+//
+// match [options] A B
+//   A and B are engines as described in arena.rs, for example "--lmr 0" or
+//   "cmd:target/release/engine --personality aggressive"
+//   --games N              number of games, at most and by default twice the number
+//                          of openings
+//   --depth N              limits of the moves, for the engines not setting them
+//   --movetime MS
+//   --plies N              openings after N moves from the empty board, 2 by default
+//   --openings FILE        openings in the format of bench/positions.txt instead
+//   --elo0 E --elo1 E      hypotheses of the SPRT on the Elo of A minus B, 0 and 20
+//   --alpha P --beta P     its error rates, 0.05
+//
+// Each opening is played twice, A playing + then -. The match stops early once the
+// SPRT accepts one of the hypotheses.
+
+// flags followed by a value, the other words are the engines
+const OPTIONS: [&str; 9] = [
+    "--games",
+    "--depth",
+    "--movetime",
+    "--plies",
+    "--openings",
+    "--elo0",
+    "--elo1",
+    "--alpha",
+    "--beta",
+];
+
+fn report(score: &Score, sprt: &Sprt) {
+    let (elo, margin) = score.elo();
+    let (lower, upper) = sprt.bounds();
+    println!(
+        "games {} A wins {} draws {} losses {} score {:.1}%",
+        score.games(),
+        score.wins,
+        score.draws,
+        score.losses,
+        100.0 * score.points() / score.games().max(1) as f64
+    );
+    println!("elo A-B {:+.1} +/- {:.1}", elo, margin);
+    println!(
+        "sprt elo0 {} elo1 {} llr {:.2} bounds [{:.2}, {:.2}] {}",
+        sprt.elo0,
+        sprt.elo1,
+        score.llr(sprt.elo0, sprt.elo1),
+        lower,
+        upper,
+        match sprt.verdict(score) {
+            Some(true) => "H1 accepted, A is stronger",
+            Some(false) => "H0 accepted, A is not stronger",
+            None => "inconclusive",
+        }
+    );
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let mut engines = Vec::new();
+    let mut i = 1;
+    while i < args.len() {
+        if OPTIONS.contains(&args[i].as_str()) {
+            i += 1;
+        } else {
+            engines.push(args[i].clone());
+        }
+        i += 1;
+    }
+    if engines.len() != 2 {
+        return Err("usage: match [options] A B".to_string());
+    }
+
+    let limits = Limits::from_args(
+        args,
+        Limits {
+            depth: None,
+            movetime: None,
+        },
+    )?;
+    let openings = arena::openings_from_args(args)?;
+    let games = option(args, "--games", 2 * openings.len())?;
+    // the engines are deterministic, more games would replay the same ones
+    if games > 2 * openings.len() {
+        return Err(format!(
+            "--games {} is more than twice the {} openings, add openings with --plies or --openings",
+            games,
+            openings.len()
+        ));
+    }
+    let sprt = Sprt {
+        elo0: option(args, "--elo0", 0.0)?,
+        elo1: option(args, "--elo1", 20.0)?,
+        alpha: option(args, "--alpha", 0.05)?,
+        beta: option(args, "--beta", 0.05)?,
+    };
+
+    let mut a = Engine::from_spec(&engines[0], limits)?;
+    let mut b = Engine::from_spec(&engines[1], limits)?;
+    println!("A {}", engines[0]);
+    println!("B {}", engines[1]);
+    println!("{} openings, {} games", openings.len(), games);
+
+    let mut score = Score::default();
+    for g in 0..games {
+        let opening = &openings[g / 2];
+        // A plays + in the even games
+        let side = if g % 2 == 0 { 1 } else { -1 };
+        let game = if side == 1 {
+            arena::play(&mut a, &mut b, opening)
+        } else {
+            arena::play(&mut b, &mut a, opening)
+        };
+        let result = game.result * side;
+        score.add(result);
+
+        let moves: Vec<String> = game.moves.iter().map(|&c| state::format_move(c)).collect();
+        println!(
+            "game {} opening {} A{} {} {}{}",
+            g + 1,
+            g / 2,
+            if side == 1 { "+" } else { "-" },
+            match result {
+                1 => "A wins",
+                0 => "draw",
+                _ => "B wins",
+            },
+            moves.join(" "),
+            game.reason.map_or(String::new(), |r| format!(" ({})", r))
+        );
+
+        // both colors of an opening are played before stopping
+        if g % 2 == 1 && sprt.verdict(&score).is_some() {
+            break;
+        }
+    }
+
+    report(&score, &sprt);
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Err(e) = run(&args) {
+        println!("{}", e);
+    }
+}
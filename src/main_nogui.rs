@@ -1,72 +1,266 @@
+extern crate connect4x4x4;
 extern crate negamax;
 extern crate time;
 
-mod state;
+use connect4x4x4::{api, bench, book, lan, search, state};
 
 use negamax::GameState;
+use std::io::BufRead;
+use std::sync::mpsc;
+use std::time::Duration;
 
 // +1 player
-fn human(x: &mut state::State) -> bool {
-    let mut mov = String::new();
-    std::io::stdin()
-        .read_line(&mut mov)
-        .expect("Failed to read line");
+fn human(x: &mut state::State, search: &mut search::Search) -> bool {
+    loop {
+        let mut mov = String::new();
+        std::io::stdin()
+            .read_line(&mut mov)
+            .expect("Failed to read line");
+
+        match mov.trim() {
+            "a" | "analyse" => {
+                println!("...");
+                search::print_analysis(&search.analyse(x, 1, 5));
+            }
+            "h" | "hint" => {
+                if let Some((col, value)) = search.hint(x, 4) {
+                    println!("hint {} {}", state::format_move(col), search::describe(value));
+                }
+            }
+            mov => {
+                return match state::parse_move(mov) {
+                    Some(col) => x.play(col, 1),
+                    None => false,
+                }
+            }
+        }
+    }
+}
 
-    let mov: i32 = match mov.trim().parse() {
-        Ok(num) => num,
-        Err(_) => return false,
+// -1 player, its moves are recorded in `game`
+fn robot(
+    x: &mut state::State,
+    search: &mut search::Search,
+    game: &mut Vec<(state::State, usize)>,
+) -> bool {
+    println!("...");
+
+    let t0 = time::precise_time_s();
+
+    // the depth counts the engine's move, the baseline searched 5 plies below it
+    let (col, best_value) = match search.think(x, -1, 6) {
+        Some(r) => r,
+        None => return false,
     };
+    game.push((x.clone(), col));
+    x.play(col, -1);
 
-    let mx = mov / 10 - 1;
-    let my = mov % 10 - 1;
+    let t1 = time::precise_time_s();
 
-    mx >= 0 && mx < 4 && my >= 0 && my < 4 && x.add(mx as usize, my as usize, 1)
+    println!(
+        "play {} {} {:.2} seconds {} values into table",
+        state::format_move(col),
+        search::describe(best_value),
+        t1 - t0,
+        search.len()
+    );
+
+    if let Some(ref t) = search.trace {
+        match t.save("search") {
+            Ok(()) => println!("{} nodes written to search.dot and search.json", t.len()),
+            Err(e) => println!("search tree: {}", e),
+        }
+    }
+
+    true
 }
 
-// -1 player
-fn robot(x: &mut state::State, table: &mut negamax::Table<state::State>) -> bool {
+// searches again the moves of a lost game and saves the losing ones in the book
+fn learn(game: &[(state::State, usize)], search: &mut search::Search) {
     println!("...");
+    let learned = search.learn(game, -1, 8);
+    match search.book.save() {
+        Ok(()) => println!(
+            "{} losing moves learned, {} in the book",
+            learned,
+            search.book.len()
+        ),
+        Err(e) => println!("book: {}", e),
+    }
+}
 
-    let mut best_value = -std::i32::MAX;
-    let mut alpha = -std::i32::MAX;
-    let beta = std::i32::MAX;
+// one JSON request per line on stdin, one response per line on stdout, see api.rs
+fn json_lines(search: search::Search) {
+    let mut session = api::Session::new(search);
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !line.trim().is_empty() {
+            println!("{}", session.handle_line(&line));
+        }
+    }
+}
 
-    let t0 = time::precise_time_s();
+// against another human over TCP, the lines typed are moves or commands
+fn lan_game(mut remote: lan::Remote) {
+    println!("xy plays, u asks to undo, y or n answers, r resigns, say TEXT chats, q quits");
 
-    let possibilities = x.possibilities(-1);
-    let n = possibilities.len();
-    let mut i = 0;
-    for y in possibilities {
-        println!("{}/{}...", i, n);
-        let v = -y.negamax_table(1, 5, -beta, -alpha, table);
-        if v > best_value {
-            best_value = v;
-            *x = y;
+    // stdin is read by a thread to keep listening to the other side
+    let (sender, lines) = mpsc::channel();
+    std::thread::spawn(move || {
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            match line {
+                Ok(line) => {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    loop {
+        let moves = remote.game.moves.len();
+        let notes = remote.update();
+        for note in notes.iter() {
+            println!("{}", note);
         }
-        if v > alpha {
-            alpha = v;
+        if !notes.is_empty() {
+            if remote.game.moves.len() != moves || remote.game.over() {
+                println!("{}", remote.game.x);
+            }
+            println!("{}", remote.status());
+        }
+
+        let line = match lines.recv_timeout(Duration::from_millis(50)) {
+            Ok(line) => line,
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        let result = match line.trim() {
+            "" => continue,
+            "q" | "quit" => break,
+            "u" | "undo" => remote.undo(),
+            "y" | "yes" => remote.answer(true),
+            "n" | "no" => remote.answer(false),
+            "r" | "resign" => remote.resign(),
+            l if l.starts_with("say ") => {
+                if let Err(e) = remote.chat(&l[4..]) {
+                    println!("{}", e);
+                }
+                continue;
+            }
+            mov => match state::parse_move(mov) {
+                Some(col) => remote.play(col),
+                None => Err(format!("unknown command {}", mov)),
+            },
+        };
+        match result {
+            Ok(()) => {
+                println!("{}", remote.game.x);
+                println!("{}", remote.status());
+            }
+            Err(e) => println!("{}", e),
         }
-        i += 1;
     }
+}
 
-    let t1 = time::precise_time_s();
+fn plain(search: &mut search::Search) {
+    search.ordering = false;
+    search.pvs = false;
+    search.aspiration = false;
+    full_width(search);
+}
 
-    println!(
-        "value={} {:.2} seconds {} values into table",
-        best_value,
-        t1 - t0,
-        table.len()
-    );
+fn ordered(search: &mut search::Search) {
+    search.pvs = false;
+    search.aspiration = false;
+    full_width(search);
+}
 
-    true
+fn pvs(search: &mut search::Search) {
+    full_width(search);
 }
 
+fn full_width(search: &mut search::Search) {
+    search.lmr = 0;
+    search.futility = 0;
+}
+
+fn reduced(_search: &mut search::Search) {}
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    match args.get(1).map(|a| a.as_str()) {
+        Some("bench") => {
+            let depth = args.get(2).and_then(|d| d.parse().ok()).unwrap_or(5);
+            bench::run(&bench::suite(), depth);
+            return;
+        }
+        Some("ordering") => {
+            bench::compare(&bench::suite(), ["plain", "ordered"], [plain, ordered], 5);
+            return;
+        }
+        Some("pvs") => {
+            bench::compare(&bench::suite(), ["ordered", "pvs"], [ordered, pvs], 5);
+            return;
+        }
+        Some("reductions") => {
+            let depth = args.get(2).and_then(|d| d.parse().ok()).unwrap_or(5);
+            bench::compare(
+                &bench::suite(),
+                ["full", "reduced"],
+                [full_width, reduced],
+                depth,
+            );
+            return;
+        }
+        _ => (),
+    }
+
     let mut x = state::State::new();
-    let mut table = negamax::Table::new();
+    let mut search = search::Search::new();
+
+    if let Err(e) = search.configure(&args) {
+        println!("{}", e);
+        return;
+    }
+    if args.get(1).map(|a| a.as_str()) == Some("json") {
+        json_lines(search);
+        return;
+    }
+    match lan::Remote::from_args(&args) {
+        Ok(Some(remote)) => {
+            lan_game(remote);
+            return;
+        }
+        Ok(None) => (),
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    }
+    // the games against the engine learn, in book.txt by default
+    if !args.iter().any(|a| a == "--book") {
+        match book::Book::load(book::BOOK) {
+            Ok(b) => search.book = b,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        }
+    }
+    println!("personality {}", search.personality().name);
 
     let mut hist: Vec<state::State> = Vec::new();
     hist.push(x.clone());
+    let mut game = Vec::new();
 
     println!("human begin ?[y/n]");
     let mut yn = String::new();
@@ -75,7 +269,7 @@ fn main() {
         .expect("Failed to read line");
 
     if yn.trim() == "n".to_string() {
-        robot(&mut x, &mut table); // player -1
+        robot(&mut x, &mut search, &mut game); // player -1
     }
 
     loop {
@@ -86,10 +280,11 @@ fn main() {
             break;
         }
 
-        let ok = human(&mut x); // player +1
+        let ok = human(&mut x, &mut search); // player +1
 
         if !ok {
             x = hist.pop().expect("empty history");
+            game.retain(|(y, _)| y.empty() > x.empty());
             println!("cancel last move");
             continue;
         }
@@ -98,10 +293,11 @@ fn main() {
 
         if x.win(1) {
             println!("the human won");
+            learn(&game, &mut search);
             break;
         }
 
-        robot(&mut x, &mut table); // player -1
+        robot(&mut x, &mut search, &mut game); // player -1
 
         hist.push(x.clone());
     }
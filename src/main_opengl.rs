@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate glium;
 
+extern crate connect4x4x4;
 extern crate eventual;
 extern crate negamax;
 extern crate time;
@@ -8,9 +9,11 @@ extern crate time;
 mod cube;
 mod glmath;
 mod sphere;
-mod state;
+
+use connect4x4x4::{book, lan, search, state};
 
 use negamax::GameState;
+use std::io::BufRead;
 
 #[derive(Clone, Copy)]
 struct Vertex {
@@ -23,7 +26,49 @@ implement_vertex!(Vertex, position, normal);
 fn main() {
     // State of the game
     let mut state = state::State::new();
-    let mut table = negamax::Table::new();
+    let mut search = search::Search::new();
+
+    let args: Vec<String> = std::env::args().collect();
+    if let Err(e) = search.configure(&args) {
+        println!("{}", e);
+        return;
+    }
+    // the games against the engine learn, in book.txt by default
+    if !args.iter().any(|a| a == "--book") {
+        match book::Book::load(book::BOOK) {
+            Ok(b) => search.book = b,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        }
+    }
+    println!("personality {}", search.personality().name);
+    // against another human, the lines typed in the console are sent as chat
+    let mut remote = match lan::Remote::from_args(&args) {
+        Ok(remote) => remote,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let (chat_sender, chat) = std::sync::mpsc::channel();
+    if remote.is_some() {
+        println!("arrows and enter play, U asks to undo, Y or N answers, R resigns");
+        std::thread::spawn(move || {
+            let stdin = std::io::stdin();
+            for line in stdin.lock().lines() {
+                match line {
+                    Ok(line) => {
+                        if chat_sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+    }
 
     use eventual::{Async, Future};
     use glium::Surface;
@@ -125,11 +170,31 @@ fn main() {
     let mut mouse_last_pos = (0.0, 0.0);
     let mut mouse_pressed = false;
     let mut key_position = (0, 0);
+    let mut hint = None; // column suggested to the human
+    let mut game: Vec<(state::State, usize)> = Vec::new(); // the engine moves so far
     let mut player_turn = 1;
 
     let mut thread = None;
 
     loop {
+        // the other human moves instead of the engine, player_turn is 0 while it has to
+        if let Some(ref mut r) = remote {
+            while let Ok(line) = chat.try_recv() {
+                if let Err(e) = r.chat(&line) {
+                    println!("{}", e);
+                }
+            }
+            let notes = r.update();
+            for note in notes.iter() {
+                println!("{}", note);
+            }
+            if !notes.is_empty() {
+                println!("{}", r.status());
+            }
+            state = r.game.x.clone();
+            player_turn = if r.our_turn() { 1 } else { 0 };
+        }
+
         let mut target = display.draw();
 
         if state.win(1) {
@@ -201,6 +266,9 @@ fn main() {
                     } else if player_turn == 1 && key_position.0 == x && key_position.1 == y {
                         high_color = [0.5, 0.5, 1.0f32];
                         dark_color = [0.5, 0.5, 0.9f32];
+                    } else if player_turn == 1 && hint == Some(x + 4 * y) {
+                        high_color = [0.5, 1.0, 0.5f32];
+                        dark_color = [0.5, 0.9, 0.5f32];
                     } else {
                         continue;
                     }
@@ -272,45 +340,58 @@ fn main() {
             // if thread not already running
             if thread.is_none() {
                 let state = state.clone();
-                let table = table.clone();
+                let search = search.clone();
+                let game = game.clone();
 
                 thread = Some(Future::spawn(move || {
                     let mut state = state.clone();
-                    let mut table = table.clone();
-
-                    let mut best_value = -std::i32::MAX;
-                    let mut alpha = -std::i32::MAX;
-                    let beta = std::i32::MAX;
+                    let mut search = search.clone();
+                    let mut game = game.clone();
+
+                    // the human won, searches again the moves of the game instead of playing
+                    if state.win(1) && !game.is_empty() {
+                        let learned = search.learn(&game, -1, 9);
+                        match search.book.save() {
+                            Ok(()) => println!(
+                                "{} losing moves learned, {} in the book",
+                                learned,
+                                search.book.len()
+                            ),
+                            Err(e) => println!("book: {}", e),
+                        }
+                        game.clear();
+                        return (state, search, game);
+                    }
 
                     let t0 = time::precise_time_s();
 
-                    let possibilities = state.possibilities(-1);
-                    let n = possibilities.len();
-                    let mut i = 0;
-                    for y in possibilities {
-                        println!("{}/{}...", i, n);
-                        let v = -y.negamax_table(1, 6, -beta, -alpha, &mut table);
-                        if v > best_value {
-                            best_value = v;
-                            state = y;
-                        }
-                        if v > alpha {
-                            alpha = v;
-                        }
-
-                        i += 1;
+                    // the depth counts the engine's move, the baseline searched 6 plies below it
+                    let best = search.think(&state, -1, 7);
+                    if let Some((col, best_value)) = best {
+                        game.push((state.clone(), col));
+                        state.play(col, -1);
+
+                        let t1 = time::precise_time_s();
+
+                        println!(
+                            "play {} {} {:.2} seconds {} values into table",
+                            state::format_move(col),
+                            search::describe(best_value),
+                            t1 - t0,
+                            search.len()
+                        );
                     }
 
-                    let t1 = time::precise_time_s();
-
-                    println!(
-                        "value={} {:.2} seconds {} values into table",
-                        best_value,
-                        t1 - t0,
-                        table.len()
-                    );
+                    if let Some(ref t) = search.trace {
+                        match t.save("search") {
+                            Ok(()) => {
+                                println!("{} nodes written to search.dot and search.json", t.len())
+                            }
+                            Err(e) => println!("search tree: {}", e),
+                        }
+                    }
 
-                    (state, table)
+                    (state, search, game)
                 }));
             }
 
@@ -318,7 +399,8 @@ fn main() {
             if thread.as_ref().map_or(false, Future::is_ready) {
                 let result = thread.unwrap().expect().unwrap();
                 state = result.0;
-                table = result.1;
+                search = result.1;
+                game = result.2;
                 thread = None;
                 player_turn = 1;
             }
@@ -371,20 +453,82 @@ fn main() {
                             }
                         }
                         VirtualKeyCode::Return | VirtualKeyCode::Space => {
-                            if player_turn == 1 {
+                            if let Some(ref mut r) = remote {
+                                match r.play(key_position.0 + 4 * key_position.1) {
+                                    Ok(()) => {
+                                        state = r.game.x.clone();
+                                        player_turn = 0;
+                                        hint = None;
+                                        println!("{}", r.status());
+                                    }
+                                    Err(e) => println!("{}", e),
+                                }
+                            } else if player_turn == 1 {
                                 if state.add(key_position.0, key_position.1, player_turn) {
                                     player_turn = -player_turn;
+                                    hint = None;
                                 }
                             }
                         }
                         VirtualKeyCode::Escape => {
-                            if player_turn == 1 {
+                            if player_turn == 1 && remote.is_none() {
                                 state = state::State::new();
                                 last_move.0 = 4;
+                                hint = None;
+                                game.clear();
                             }
                         }
                         VirtualKeyCode::P => {
-                            player_turn = -1;
+                            if remote.is_none() {
+                                player_turn = -1;
+                                hint = None;
+                            }
+                        }
+                        VirtualKeyCode::U
+                        | VirtualKeyCode::Y
+                        | VirtualKeyCode::N
+                        | VirtualKeyCode::R => {
+                            if let Some(ref mut r) = remote {
+                                let result = match key_code {
+                                    VirtualKeyCode::U => r.undo(),
+                                    VirtualKeyCode::Y => r.answer(true),
+                                    VirtualKeyCode::N => r.answer(false),
+                                    _ => r.resign(),
+                                };
+                                match result {
+                                    Ok(()) => println!("{}", r.status()),
+                                    Err(e) => println!("{}", e),
+                                }
+                            }
+                        }
+                        VirtualKeyCode::H => {
+                            if player_turn == 1 {
+                                // the local side in a LAN game, + against the engine
+                                let side = remote.as_ref().map_or(1, |r| r.game.side);
+                                let best = if side == 1 {
+                                    search.hint(&state, 4)
+                                } else {
+                                    search.think(&state, side, 4)
+                                };
+                                hint = best.map(|(col, value)| {
+                                    println!(
+                                        "hint {} {}",
+                                        state::format_move(col),
+                                        search::describe(value)
+                                    );
+                                    col
+                                });
+                            }
+                        }
+                        VirtualKeyCode::A => {
+                            // printed on the console when the search is done
+                            let state = state.clone();
+                            let mut search = search.clone();
+                            // player_turn is 0 while the other side of a LAN game is to move
+                            let player = remote.as_ref().map_or(player_turn, |r| r.game.player());
+                            std::thread::spawn(move || {
+                                search::print_analysis(&search.analyse(&state, player, 6));
+                            });
                         }
                         _ => (),
                     },
@@ -0,0 +1,323 @@
+extern crate connect4x4x4;
+
+use connect4x4x4::api::Session;
+use connect4x4x4::http::{Events, Request, Response};
+use connect4x4x4::json;
+use connect4x4x4::json::Value;
+use connect4x4x4::search::Search;
+use std::collections::BTreeMap;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// REST server on localhost, every game is a Session of api.rs kept in memory
+//
+// GET    /                      the web page of web/index.html
+// GET    /games                 the games with their position, "busy" for those searching
+// POST   /games                 new game, the body can give {"state":STATE,"player":"-"}
+// GET    /games/ID              position, result and history
+// DELETE /games/ID
+// GET    /games/ID/legal        legal moves
+// POST   /games/ID/moves        plays {"move":"23"}
+// POST   /games/ID/undo
+// POST   /games/ID/engine       the engine plays, {"movetime":MS} or {"depth":N}, never
+//                                longer than MAX_MOVETIME
+// GET    /games/ID/engine/stream?movetime=MS|depth=N
+//                                same as server-sent events: an "info" event after each
+//                                iteration, then a "move" event with the response
+//
+// Responses are the JSON objects of api.rs, errors answer 400 or 404 with "error",
+// 403 for the requests of pages from another origin or naming another host than
+// 127.0.0.1:PORT or localhost:PORT.
+
+const PORT: u16 = 4444;
+// time given to the engine when the request has no limit
+const MOVETIME: f64 = 1000.0;
+// the longest search, the game is locked meanwhile
+const MAX_MOVETIME: f64 = 10000.0;
+// seconds a client has to send its request
+const READ_TIMEOUT: u64 = 10;
+
+struct Games {
+    next: u64,
+    sessions: BTreeMap<u64, Arc<Mutex<Session>>>,
+}
+
+type Shared = Arc<Mutex<Games>>;
+
+// the answer to a request, the engine streams are written while it searches
+enum Reply {
+    Response(Response),
+    Stream(Arc<Mutex<Session>>, Value),
+}
+
+impl From<Response> for Reply {
+    fn from(r: Response) -> Reply {
+        Reply::Response(r)
+    }
+}
+
+// the request body as an object, an empty body is an empty object
+fn body(request: &Request) -> Result<Value, Response> {
+    if request.body.trim().is_empty() {
+        return Ok(Value::Object(Vec::new()));
+    }
+    match json::parse(&request.body) {
+        Ok(v @ Value::Object(_)) => Ok(v),
+        Ok(_) => Err(Response::error(400, "the body must be an object")),
+        Err(e) => Err(Response::error(400, &format!("invalid JSON: {}", e))),
+    }
+}
+
+// the fields of `body` with the command `cmd`
+fn command(cmd: &str, body: Value) -> Value {
+    let mut fields = vec![("cmd".to_string(), Value::from(cmd))];
+    if let Value::Object(f) = body {
+        fields.extend(f.into_iter().filter(|f| f.0 != "cmd"));
+    }
+    Value::Object(fields)
+}
+
+// the search command playing the move, with the default movetime and at most
+// MAX_MOVETIME even with a depth
+fn engine(body: Value) -> Value {
+    let mut request = command("search", body);
+    if let Value::Object(ref mut f) = request {
+        let depth = f.iter().any(|f| f.0 == "depth");
+        match f.iter_mut().find(|f| f.0 == "movetime") {
+            Some(&mut (_, Value::Number(ref mut t))) => *t = t.min(MAX_MOVETIME),
+            // not a number, the session answers the error
+            Some(_) => (),
+            None => {
+                let t = if depth { MAX_MOVETIME } else { MOVETIME };
+                f.push(("movetime".to_string(), Value::from(t)));
+            }
+        }
+        f.push(("play".to_string(), Value::Bool(true)));
+    }
+    request
+}
+
+// runs the command on the session, 400 when it fails
+fn run(session: &Mutex<Session>, request: Value, status: u16) -> Response {
+    let response = session.lock().unwrap().handle(&request);
+    let status = if response.get("ok") == Some(&Value::Bool(true)) {
+        status
+    } else {
+        400
+    };
+    Response::json(status, &response)
+}
+
+fn route(request: &Request, games: &Shared, template: &Search, port: u16) -> Reply {
+    // a site whose name was pointed at 127.0.0.1 sends that name as the host, and as the
+    // origin of its pages, so the host has to be the server's own address
+    let host = request.header("host").unwrap_or("");
+    if host != format!("127.0.0.1:{}", port) && host != format!("localhost:{}", port) {
+        return Response::error(403, "unknown host").into();
+    }
+    // browsers tell the page a request comes from, only the pages of the server are
+    // allowed to drive it
+    if let Some(origin) = request.header("origin") {
+        if origin != format!("http://{}", host) {
+            return Response::error(403, "cross-origin request").into();
+        }
+    }
+
+    let path: Vec<&str> = request.path.split('/').filter(|p| !p.is_empty()).collect();
+    let method = request.method.as_str();
+
+    if path.is_empty() {
+        return match method {
+            "GET" => Response::html(include_str!("../web/index.html")),
+            _ => Response::error(405, "method not allowed"),
+        }
+        .into();
+    }
+
+    if path.first() != Some(&"games") {
+        return Response::error(404, "not found").into();
+    }
+
+    if path.len() == 1 {
+        return match method {
+            "GET" => {
+                // the sessions are locked one by one, a game searching is only said to be busy
+                let sessions: Vec<(u64, Arc<Mutex<Session>>)> = games
+                    .lock()
+                    .unwrap()
+                    .sessions
+                    .iter()
+                    .map(|(&id, session)| (id, session.clone()))
+                    .collect();
+                let list: Vec<Value> = sessions
+                    .into_iter()
+                    .map(|(id, session)| {
+                        let mut fields = vec![("game".to_string(), Value::from(id))];
+                        match session.try_lock() {
+                            Ok(mut session) => {
+                                if let Value::Object(f) =
+                                    session.handle(&command("state", Value::Null))
+                                {
+                                    fields.extend(f);
+                                }
+                            }
+                            Err(_) => fields.push(("busy".to_string(), Value::Bool(true))),
+                        }
+                        Value::Object(fields)
+                    })
+                    .collect();
+                Response::json(200, &json::object(vec![("games", Value::Array(list))]))
+            }
+            "POST" => {
+                let body = match body(request) {
+                    Ok(body) => body,
+                    Err(r) => return r.into(),
+                };
+                let mut session = Session::new(template.clone());
+                let mut response = session.handle(&command("new", body));
+                if response.get("ok") != Some(&Value::Bool(true)) {
+                    return Response::json(400, &response).into();
+                }
+                let mut games = games.lock().unwrap();
+                let id = games.next;
+                games.next += 1;
+                games.sessions.insert(id, Arc::new(Mutex::new(session)));
+                if let Value::Object(ref mut f) = response {
+                    f.insert(0, ("game".to_string(), Value::from(id)));
+                }
+                Response::json(201, &response)
+            }
+            _ => Response::error(405, "method not allowed"),
+        }
+        .into();
+    }
+
+    let id: u64 = match path[1].parse() {
+        Ok(id) => id,
+        Err(_) => return Response::error(404, "not found").into(),
+    };
+    let session = match games.lock().unwrap().sessions.get(&id) {
+        Some(session) => session.clone(),
+        None => return Response::error(404, &format!("no game {}", id)).into(),
+    };
+
+    if method == "GET" && path[2..] == ["engine", "stream"] {
+        // an EventSource has no body, the limits are in the query
+        let mut fields = Vec::new();
+        for name in ["movetime", "depth"].iter() {
+            if let Some(p) = request.param(name) {
+                match p.parse::<f64>() {
+                    Ok(n) => fields.push((name.to_string(), Value::from(n))),
+                    Err(_) => {
+                        return Response::error(400, &format!("{} must be a number", name)).into()
+                    }
+                }
+            }
+        }
+        return Reply::Stream(session, engine(Value::Object(fields)));
+    }
+
+    let body = match body(request) {
+        Ok(body) => body,
+        Err(r) => return r.into(),
+    };
+    let response = match (method, &path[2..]) {
+        ("GET", &[]) => run(&session, command("history", body), 200),
+        ("DELETE", &[]) => {
+            games.lock().unwrap().sessions.remove(&id);
+            Response::json(200, &json::object(vec![("ok", true.into())]))
+        }
+        ("GET", &["legal"]) => run(&session, command("legal", body), 200),
+        ("POST", &["moves"]) => run(&session, command("play", body), 200),
+        ("POST", &["undo"]) => run(&session, command("undo", body), 200),
+        ("POST", &["engine"]) => run(&session, engine(body), 200),
+        (_, &[])
+        | (_, &["legal"])
+        | (_, &["moves"])
+        | (_, &["undo"])
+        | (_, &["engine"])
+        | (_, &["engine", "stream"]) => Response::error(405, "method not allowed"),
+        _ => Response::error(404, "not found"),
+    };
+    response.into()
+}
+
+// runs the search on the session and writes its iterations as events
+fn stream(stream: &TcpStream, session: &Mutex<Session>, request: &Value) -> std::io::Result<()> {
+    let mut events = Events::start(stream)?;
+    let mut session = session.lock().unwrap();
+    // a closed page stops the search
+    let stop = session.search.stop.clone();
+    let response = session.search(request, |info| {
+        if events.send("info", &info).is_err() {
+            stop.store(true, Ordering::Relaxed);
+        }
+    });
+    session.search.stop.store(false, Ordering::Relaxed);
+    events.send("move", &response)
+}
+
+fn serve(s: TcpStream, games: &Shared, template: &Search, port: u16) {
+    // a client that sends nothing does not keep its thread forever
+    if let Err(e) = s.set_read_timeout(Some(Duration::from_secs(READ_TIMEOUT))) {
+        println!("{}", e);
+        return;
+    }
+    let reply = match Request::read(&s) {
+        Ok(request) => route(&request, games, template, port),
+        Err(e) => Response::error(400, &e).into(),
+    };
+    let result = match reply {
+        Reply::Response(response) => response.write_to(&s),
+        Reply::Stream(session, request) => stream(&s, &session, &request),
+    };
+    if let Err(e) = result {
+        println!("{}", e);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut template = Search::new();
+    if let Err(e) = template.configure(&args) {
+        println!("{}", e);
+        return;
+    }
+    let port = match args.iter().position(|a| a == "--port") {
+        Some(i) => match args.get(i + 1).and_then(|p| p.parse().ok()) {
+            Some(port) => port,
+            None => {
+                println!("--port needs a number");
+                return;
+            }
+        },
+        None => PORT,
+    };
+
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("127.0.0.1:{}: {}", port, e);
+            return;
+        }
+    };
+    println!("listening on http://127.0.0.1:{}", port);
+
+    let template = Arc::new(template);
+    let games = Arc::new(Mutex::new(Games {
+        next: 1,
+        sessions: BTreeMap::new(),
+    }));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let games = games.clone();
+        let template = template.clone();
+        std::thread::spawn(move || serve(stream, &games, &template, port));
+    }
+}
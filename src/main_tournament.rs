@@ -0,0 +1,174 @@
+extern crate connect4x4x4;
+
+use connect4x4x4::arena::{option, Limits};
+use connect4x4x4::tournament::{Job, Player, Table};
+use connect4x4x4::{arena, tournament};
+use std::io::Write;
+
+// Round robin or Swiss tournament between engines
+//
+// tournament [options] NAME=SPEC NAME=SPEC ...
+//   the SPEC of an engine is described in arena.rs, for example base= nolmr=--lmr 0
+//   --engines FILE     more engines, one per line: the name then the spec
+//   --swiss            Swiss pairings instead of round robin
+//   --rounds N         rounds of the Swiss tournament, log2 of the engines plus 1 by default
+//   --cycles N         times everyone meets everyone in round robin, 1 by default
+//   --threads N        games played at the same time, 1 by default
+//   --depth N          limits of the moves, for the engines not setting them
+//   --movetime MS
+//   --plies N          openings after N moves from the empty board, 2 by default
+//   --openings FILE    openings in the format of bench/positions.txt instead
+//   --record FILE      where the games are written, tournament.txt by default
+//
+// A pairing is two games from the same opening with the colors swapped, the openings
+// are taken in turn and each is used once at most. The standings follow each round, the crosstable and the ratings
+// end the tournament.
+
+// flags followed by a value, the other words are the engines
+const OPTIONS: [&str; 9] = [
+    "--engines",
+    "--rounds",
+    "--cycles",
+    "--threads",
+    "--depth",
+    "--movetime",
+    "--plies",
+    "--openings",
+    "--record",
+];
+
+fn run(args: &[String]) -> Result<(), String> {
+    let mut players = match args.iter().position(|a| a == "--engines") {
+        Some(i) => tournament::load_players(args.get(i + 1).ok_or("--engines needs a file")?)?,
+        None => Vec::new(),
+    };
+    let mut i = 1;
+    while i < args.len() {
+        if OPTIONS.contains(&args[i].as_str()) {
+            i += 1;
+        } else if args[i] != "--swiss" {
+            let player = Player::parse(&args[i])?;
+            if players.iter().any(|p| p.name == player.name) {
+                return Err(format!("{} twice", player.name));
+            }
+            players.push(player);
+        }
+        i += 1;
+    }
+    if players.len() < 2 {
+        return Err("usage: tournament [options] NAME=SPEC NAME=SPEC ...".to_string());
+    }
+
+    let limits = Limits::from_args(
+        args,
+        Limits {
+            depth: None,
+            movetime: None,
+        },
+    )?;
+    let openings = arena::openings_from_args(args)?;
+    let threads = option(args, "--threads", 1)?;
+    let swiss = args.iter().any(|a| a == "--swiss");
+    let n = players.len();
+    let rounds = if swiss {
+        let log2 = (0..).find(|&k| 1 << k >= n).unwrap_or(0);
+        option(args, "--rounds", log2 + 1)?
+    } else {
+        option(args, "--cycles", 1)? * (n + n % 2 - 1)
+    };
+    // the engines are deterministic, an opening played again would replay the same games
+    let pairings = rounds * (n / 2);
+    if pairings > openings.len() {
+        return Err(format!(
+            "{} pairings for {} openings, add openings with --plies or --openings",
+            pairings,
+            openings.len()
+        ));
+    }
+    let path = match args.iter().position(|a| a == "--record") {
+        Some(i) => args.get(i + 1).ok_or("--record needs a file")?.as_str(),
+        None => "tournament.txt",
+    };
+    let mut record = std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+
+    // an engine failing to start stops the tournament before the first round
+    for p in players.iter() {
+        arena::Engine::from_spec(&p.spec, limits).map_err(|e| format!("{}: {}", p.name, e))?;
+    }
+    for p in players.iter() {
+        println!("{:16} {}", p.name, p.spec);
+    }
+    println!(
+        "{} {} rounds, {} openings, games written to {}",
+        if swiss { "swiss" } else { "round robin" },
+        rounds,
+        openings.len(),
+        path
+    );
+
+    let robin = tournament::round_robin(n);
+    let mut table = Table::new(n);
+    let mut pairing = 0;
+    for r in 0..rounds {
+        let round = if swiss {
+            table.swiss()
+        } else {
+            robin[r % robin.len()].clone()
+        };
+        if let Some(b) = round.bye {
+            table.byes[b] += 1;
+        }
+        let mut jobs = Vec::new();
+        for &(a, b) in round.pairs.iter() {
+            let opening = pairing;
+            pairing += 1;
+            jobs.push(Job {
+                plus: a,
+                minus: b,
+                opening,
+            });
+            jobs.push(Job {
+                plus: b,
+                minus: a,
+                opening,
+            });
+        }
+
+        let mut error = None;
+        tournament::play(&players, &openings, limits, jobs, threads, |job, game| {
+            table.add(job.plus, job.minus, &game);
+            let tags = [
+                ("Event", "tournament".to_string()),
+                ("Round", (r + 1).to_string()),
+                ("Plus", players[job.plus].name.clone()),
+                ("Minus", players[job.minus].name.clone()),
+            ];
+            if let Err(e) = record.write_all(game.record(&tags).as_bytes()) {
+                error = Some(format!("{}: {}", path, e));
+            }
+        })?;
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        let standings: Vec<String> = table
+            .standings()
+            .iter()
+            .map(|&i| format!("{} {}", players[i].name, table.points(i)))
+            .collect();
+        println!("round {} {}", r + 1, standings.join(", "));
+    }
+
+    println!();
+    tournament::print_crosstable(&players, &table);
+    println!();
+    tournament::print_ratings(&players, &table);
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Err(e) = run(&args) {
+        println!("{}", e);
+    }
+}
@@ -0,0 +1,72 @@
+extern crate connect4x4x4;
+extern crate time;
+
+use connect4x4x4::network::Network;
+use connect4x4x4::rng;
+use connect4x4x4::rng::Rng;
+use connect4x4x4::selfplay;
+use std::str::FromStr;
+
+// value following `name` on the command line
+fn option<T: FromStr>(args: &[String], name: &str, default: T) -> T {
+    match args.iter().position(|a| a == name) {
+        Some(i) => match args.get(i + 1).and_then(|v| v.parse().ok()) {
+            Some(v) => v,
+            None => {
+                println!("invalid value for {}", name);
+                std::process::exit(1);
+            }
+        },
+        None => default,
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut config = selfplay::Config::new();
+    config.iterations = option(&args, "--iterations", config.iterations);
+    config.games = option(&args, "--games", config.games);
+    config.simulations = option(&args, "--simulations", config.simulations);
+    config.steps = option(&args, "--steps", config.steps);
+    config.rate = option(&args, "--rate", config.rate);
+    config.gating_games = option(&args, "--gating-games", config.gating_games);
+    config.negamax_games = option(&args, "--negamax-games", config.negamax_games);
+    config.negamax_depth = option(&args, "--negamax-depth", config.negamax_depth);
+    let out: String = option(&args, "--out", "network.txt".to_string());
+    let seed = match rng::seed_from_args(&args) {
+        Ok(seed) => seed,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    println!("seed {}", seed);
+    let mut rng = Rng::new(seed);
+
+    let network = match args.iter().position(|a| a == "--network") {
+        Some(i) => match args.get(i + 1).map(|path| Network::load(path)) {
+            Some(Ok(network)) => network,
+            Some(Err(e)) => {
+                println!("{}", e);
+                return;
+            }
+            None => {
+                println!("--network needs a weights file");
+                return;
+            }
+        },
+        None => {
+            let mut sizes = vec![64];
+            sizes.extend(config.hidden.iter().cloned());
+            sizes.push(17);
+            Network::new(&sizes, &mut rng)
+        }
+    };
+
+    let t0 = time::precise_time_s();
+    selfplay::train(&config, network, &out, &mut rng);
+    let t1 = time::precise_time_s();
+
+    println!("{:.2} seconds", t1 - t0);
+}
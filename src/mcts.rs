@@ -0,0 +1,149 @@
+use negamax::GameState;
+use network::Network;
+use rng::Rng;
+use state::State;
+
+// Monte Carlo tree search guided by the policy and the value of a network (PUCT)
+
+struct Node {
+    col: usize,
+    prior: f32,
+    visits: u32,
+    // sum of the values in the perspective of the player who played `col`
+    value: f32,
+    children: Vec<usize>,
+    expanded: bool,
+}
+
+impl Node {
+    fn new(col: usize, prior: f32) -> Node {
+        Node {
+            col,
+            prior,
+            visits: 0,
+            value: 0.0,
+            children: Vec::new(),
+            expanded: false,
+        }
+    }
+}
+
+pub struct Mcts<'a> {
+    network: &'a Network,
+    pub simulations: u32,
+    pub c_puct: f32,
+    // weight of the noise added to the priors of the root, 0 to disable
+    pub noise: f32,
+}
+
+impl<'a> Mcts<'a> {
+    pub fn new(network: &'a Network, simulations: u32) -> Mcts<'a> {
+        Mcts {
+            network,
+            simulations,
+            c_puct: 1.5,
+            noise: 0.0,
+        }
+    }
+
+    // value of a position for the player to move if the game is over
+    fn terminal(x: &State, player: i32) -> Option<f32> {
+        if x.win(-player) {
+            Some(-1.0)
+        } else if x.full() {
+            Some(0.0)
+        } else {
+            None
+        }
+    }
+
+    fn expand(&self, nodes: &mut Vec<Node>, id: usize, x: &State, player: i32) -> f32 {
+        let (policy, value) = self.network.policy_value(x, player);
+        for (col, &prior) in policy.iter().enumerate() {
+            if x.playable(col) {
+                nodes.push(Node::new(col, prior));
+                let child = nodes.len() - 1;
+                nodes[id].children.push(child);
+            }
+        }
+        nodes[id].expanded = true;
+        value
+    }
+
+    fn select(&self, nodes: &[Node], id: usize) -> usize {
+        let sqrt_n = (nodes[id].visits as f32).max(1.0).sqrt();
+        let mut best = nodes[id].children[0];
+        let mut best_score = f32::NEG_INFINITY;
+        for &c in nodes[id].children.iter() {
+            let child = &nodes[c];
+            let q = if child.visits > 0 {
+                child.value / child.visits as f32
+            } else {
+                0.0
+            };
+            let u = self.c_puct * child.prior * sqrt_n / (1.0 + child.visits as f32);
+            if q + u > best_score {
+                best_score = q + u;
+                best = c;
+            }
+        }
+        best
+    }
+
+    // visit counts of the columns after the simulations, normalized to sum to one
+    pub fn search(&self, x: &State, player: i32, rng: &mut Rng) -> [f32; 16] {
+        let mut nodes = vec![Node::new(16, 1.0)];
+        let mut pi = [0.0; 16];
+        if Mcts::terminal(x, player).is_some() {
+            return pi;
+        }
+        self.expand(&mut nodes, 0, x, player);
+
+        if self.noise > 0.0 {
+            // Dirichlet(1) noise, normalized exponential variables
+            let noise: Vec<f32> = nodes[0]
+                .children
+                .iter()
+                .map(|_| -(1.0 - rng.next_f32()).ln())
+                .collect();
+            let total: f32 = noise.iter().sum();
+            let children = nodes[0].children.clone();
+            for (&c, eta) in children.iter().zip(noise) {
+                nodes[c].prior = (1.0 - self.noise) * nodes[c].prior + self.noise * eta / total;
+            }
+        }
+
+        for _ in 0..self.simulations {
+            let mut path = vec![0];
+            let mut y = x.clone();
+            let mut p = player;
+
+            let mut id = 0;
+            let mut value = loop {
+                if let Some(v) = Mcts::terminal(&y, p) {
+                    break v;
+                }
+                if !nodes[id].expanded {
+                    break self.expand(&mut nodes, id, &y, p);
+                }
+                id = self.select(&nodes, id);
+                y.play(nodes[id].col, p);
+                p = -p;
+                path.push(id);
+            };
+
+            // `value` is in the perspective of `p`, the player to move at the leaf
+            for &id in path.iter().rev() {
+                nodes[id].visits += 1;
+                nodes[id].value -= value;
+                value = -value;
+            }
+        }
+
+        let total: u32 = nodes[0].children.iter().map(|&c| nodes[c].visits).sum();
+        for &c in nodes[0].children.iter() {
+            pi[nodes[c].col] = nodes[c].visits as f32 / total.max(1) as f32;
+        }
+        pi
+    }
+}
@@ -0,0 +1,294 @@
+use rng::Rng;
+use state::State;
+use std;
+use std::io::{Read, Write};
+
+// Fully connected network evaluating a position for the player to move
+//
+// The input is the 64 cells of the state seen by the player to move (+1 own, -1 opponent, 0 empty),
+// hidden layers use relu. The last output goes through tanh and is the value: +1 is a sure win
+// for the player to move, -1 a sure loss. A network with 17 outputs also has a policy head,
+// the first 16 outputs are the logits of the columns x + 4 * y.
+//
+// Weights file: the words `network VERSION`, then numbers separated by whitespace,
+// the number of layers n followed by the n + 1 layer sizes (64 first, 1 or 17 last),
+// then for each layer its weights row by row (outputs x inputs) and its biases.
+// The files without the header come from the first version, whose input was the state
+// in the perspective of +1, they are rejected.
+
+// value given to a sure win, the same as a completed line for State::value
+const SCALE: f32 = 76.0 * 76.0 * 76.0;
+
+// version of the weights file, it changes with the meaning of the weights
+const VERSION: u32 = 2;
+
+#[derive(Clone)]
+struct Layer {
+    inputs: usize,
+    outputs: usize,
+    weights: Vec<f32>,
+    biases: Vec<f32>,
+}
+
+impl Layer {
+    fn forward(&self, input: &[f32], relu: bool) -> Vec<f32> {
+        let mut output = self.biases.clone();
+        for (o, out) in output.iter_mut().enumerate() {
+            let row = &self.weights[o * self.inputs..(o + 1) * self.inputs];
+            for (w, i) in row.iter().zip(input) {
+                *out += w * i;
+            }
+            if relu && *out < 0.0 {
+                *out = 0.0;
+            }
+        }
+        output
+    }
+}
+
+// a training position, seen by the player to move
+#[derive(Clone)]
+pub struct Sample {
+    pub state: State,
+    pub policy: [f32; 16],
+    pub value: f32,
+}
+
+#[derive(Clone)]
+pub struct Network {
+    layers: Vec<Layer>,
+}
+
+pub fn input(x: &State, player: i32) -> Vec<f32> {
+    x.cells().iter().map(|&c| (player * c) as f32).collect()
+}
+
+// softmax over the legal columns only
+fn masked_softmax(logits: &[f32], x: &State) -> [f32; 16] {
+    let mut p = [0.0; 16];
+    let mut max = f32::NEG_INFINITY;
+    for (col, &logit) in logits.iter().enumerate() {
+        if x.playable(col) && logit > max {
+            max = logit;
+        }
+    }
+    let mut total = 0.0;
+    for col in 0..16 {
+        if x.playable(col) {
+            p[col] = (logits[col] - max).exp();
+            total += p[col];
+        }
+    }
+    if total > 0.0 {
+        for v in p.iter_mut() {
+            *v /= total;
+        }
+    }
+    p
+}
+
+impl Network {
+    // random weights, He initialization
+    pub fn new(sizes: &[usize], rng: &mut Rng) -> Network {
+        let mut layers = Vec::new();
+        for l in 0..sizes.len() - 1 {
+            let std = (2.0 / sizes[l] as f32).sqrt();
+            layers.push(Layer {
+                inputs: sizes[l],
+                outputs: sizes[l + 1],
+                weights: (0..sizes[l] * sizes[l + 1])
+                    .map(|_| std * rng.normal())
+                    .collect(),
+                biases: vec![0.0; sizes[l + 1]],
+            });
+        }
+        Network { layers }
+    }
+
+    pub fn load(path: &str) -> Result<Network, String> {
+        let mut text = String::new();
+        std::fs::File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut text))
+            .map_err(|e| format!("{}: {}", path, e))?;
+        Network::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<Network, String> {
+        let mut numbers = text.split_whitespace();
+        match (numbers.next(), numbers.next()) {
+            (Some("network"), Some(v)) if v == VERSION.to_string() => {}
+            (Some("network"), Some(v)) => {
+                return Err(format!("weights of version {}, expected {}", v, VERSION))
+            }
+            _ => {
+                return Err(format!(
+                    "weights of version 1 or not weights, expected version {}",
+                    VERSION
+                ))
+            }
+        }
+
+        let mut next = || -> Result<f32, String> {
+            match numbers.next() {
+                Some(s) => s.parse().map_err(|_| format!("invalid number {}", s)),
+                None => Err("unexpected end of file".to_string()),
+            }
+        };
+
+        let n = next()? as usize;
+        let mut sizes = Vec::new();
+        for _ in 0..n + 1 {
+            sizes.push(next()? as usize);
+        }
+        if n == 0 || sizes[0] != 64 || (sizes[n] != 1 && sizes[n] != 17) {
+            return Err("layer sizes must go from 64 to 1 or 17".to_string());
+        }
+
+        let mut layers = Vec::new();
+        for l in 0..n {
+            let mut layer = Layer {
+                inputs: sizes[l],
+                outputs: sizes[l + 1],
+                weights: Vec::new(),
+                biases: Vec::new(),
+            };
+            for _ in 0..layer.inputs * layer.outputs {
+                layer.weights.push(next()?);
+            }
+            for _ in 0..layer.outputs {
+                layer.biases.push(next()?);
+            }
+            layers.push(layer);
+        }
+
+        Ok(Network { layers })
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut f = std::fs::File::create(path)?;
+        writeln!(f, "network {}", VERSION)?;
+        write!(f, "{}", self.layers.len())?;
+        write!(f, " {}", self.layers[0].inputs)?;
+        for layer in self.layers.iter() {
+            write!(f, " {}", layer.outputs)?;
+        }
+        writeln!(f)?;
+        for layer in self.layers.iter() {
+            for row in layer.weights.chunks(layer.inputs) {
+                let row: Vec<String> = row.iter().map(|w| w.to_string()).collect();
+                writeln!(f, "{}", row.join(" "))?;
+            }
+            let biases: Vec<String> = layer.biases.iter().map(|b| b.to_string()).collect();
+            writeln!(f, "{}", biases.join(" "))?;
+        }
+        Ok(())
+    }
+
+    pub fn has_policy(&self) -> bool {
+        self.layers[self.layers.len() - 1].outputs == 17
+    }
+
+    fn outputs(&self, x: &State, player: i32) -> Vec<f32> {
+        let mut a = input(x, player);
+        let n = self.layers.len();
+        for (l, layer) in self.layers.iter().enumerate() {
+            a = layer.forward(&a, l + 1 < n);
+        }
+        a
+    }
+
+    // in [-1, 1] in the perspective of `player`, the player to move
+    pub fn forward(&self, x: &State, player: i32) -> f32 {
+        let a = self.outputs(x, player);
+        a[a.len() - 1].tanh()
+    }
+
+    // same scale as State::value, in the perspective of `player`
+    pub fn value(&self, x: &State, player: i32) -> i32 {
+        (self.forward(x, player) * SCALE) as i32
+    }
+
+    // probabilities of the legal columns, uniform without a policy head, and the value
+    pub fn policy_value(&self, x: &State, player: i32) -> ([f32; 16], f32) {
+        let a = self.outputs(x, player);
+        let value = a[a.len() - 1].tanh();
+        if self.has_policy() {
+            (masked_softmax(&a[..16], x), value)
+        } else {
+            (masked_softmax(&[0.0; 16], x), value)
+        }
+    }
+
+    // one step of gradient descent on the batch, returns the mean loss
+    // loss = (value - target)^2 + cross entropy of the policy
+    pub fn train(&mut self, batch: &[&Sample], rate: f32, decay: f32) -> f32 {
+        let n = self.layers.len();
+        let mut grad_w: Vec<Vec<f32>> = self.layers.iter().map(|l| vec![0.0; l.weights.len()]).collect();
+        let mut grad_b: Vec<Vec<f32>> = self.layers.iter().map(|l| vec![0.0; l.biases.len()]).collect();
+        let mut loss = 0.0;
+
+        for sample in batch {
+            // forward, keeping the activations
+            let mut acts = vec![input(&sample.state, 1)];
+            for (l, layer) in self.layers.iter().enumerate() {
+                let a = layer.forward(&acts[l], l + 1 < n);
+                acts.push(a);
+            }
+            let out = &acts[n];
+            let last = out.len() - 1;
+
+            // gradient of the loss with respect to the outputs
+            let mut delta = vec![0.0; out.len()];
+            let v = out[last].tanh();
+            loss += (v - sample.value) * (v - sample.value);
+            delta[last] = 2.0 * (v - sample.value) * (1.0 - v * v);
+            if self.has_policy() {
+                let p = masked_softmax(&out[..16], &sample.state);
+                for col in 0..16 {
+                    if sample.state.playable(col) {
+                        delta[col] = p[col] - sample.policy[col];
+                        if sample.policy[col] > 0.0 {
+                            loss -= sample.policy[col] * p[col].max(1e-12).ln();
+                        }
+                    }
+                }
+            }
+
+            // backward
+            for l in (0..n).rev() {
+                let layer = &self.layers[l];
+                let a = &acts[l];
+                let mut previous = vec![0.0; layer.inputs];
+                for o in 0..layer.outputs {
+                    if delta[o] == 0.0 {
+                        continue;
+                    }
+                    grad_b[l][o] += delta[o];
+                    for i in 0..layer.inputs {
+                        grad_w[l][o * layer.inputs + i] += delta[o] * a[i];
+                        previous[i] += delta[o] * layer.weights[o * layer.inputs + i];
+                    }
+                }
+                // relu of the layer below, its output is zero where it was cut
+                for i in 0..layer.inputs {
+                    if l > 0 && a[i] <= 0.0 {
+                        previous[i] = 0.0;
+                    }
+                }
+                delta = previous;
+            }
+        }
+
+        let scale = rate / batch.len() as f32;
+        for (l, layer) in self.layers.iter_mut().enumerate() {
+            for (w, g) in layer.weights.iter_mut().zip(grad_w[l].iter()) {
+                *w -= scale * g + rate * decay * *w;
+            }
+            for (b, g) in layer.biases.iter_mut().zip(grad_b[l].iter()) {
+                *b -= scale * g;
+            }
+        }
+
+        loss / batch.len() as f32
+    }
+}
@@ -0,0 +1,82 @@
+use state::State;
+
+// Engine personalities
+//
+// The lines are counted separately for the engine and for its opponent and weighted in
+// percent: an aggressive engine values its own threats more than it fears the opponent's,
+// a defensive one the other way around. The contempt is what a draw costs the engine,
+// positive to avoid draws and negative to seek them.
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Personality {
+    pub name: &'static str,
+    pub attack: i32,   // weight of the engine's lines, in percent
+    pub defence: i32,  // weight of the opponent's lines, in percent
+    pub contempt: i32, // a draw is worth -contempt to the engine
+}
+
+pub static PERSONALITIES: [Personality; 3] = [
+    Personality {
+        name: "balanced",
+        attack: 100,
+        defence: 100,
+        contempt: 0,
+    },
+    Personality {
+        name: "aggressive",
+        attack: 150,
+        defence: 75,
+        contempt: 76 * 76,
+    },
+    Personality {
+        name: "defensive",
+        attack: 75,
+        defence: 150,
+        contempt: -76 * 76,
+    },
+];
+
+impl Personality {
+    pub fn balanced() -> Personality {
+        PERSONALITIES[0]
+    }
+
+    pub fn by_name(name: &str) -> Result<Personality, String> {
+        match PERSONALITIES.iter().find(|p| p.name == name) {
+            Some(&p) => Ok(p),
+            None => {
+                let names: Vec<&str> = PERSONALITIES.iter().map(|p| p.name).collect();
+                Err(format!(
+                    "unknown personality {}, expected one of {}",
+                    name,
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+
+    // the values do not depend on which side is the engine
+    pub fn is_symmetric(&self) -> bool {
+        self.attack == self.defence && self.contempt == 0
+    }
+
+    // value of the lines in the perspective of the engine playing `side`
+    pub fn lines(&self, x: &State, side: i32) -> i32 {
+        let (plus, minus) = x.lines();
+        let (own, other) = if side == 1 {
+            (plus, minus)
+        } else {
+            (minus, plus)
+        };
+        (own * self.attack - other * self.defence) / 100
+    }
+
+    // value of a draw for `player` when the engine plays `side`
+    pub fn draw(&self, player: i32, side: i32) -> i32 {
+        if player == side {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+}
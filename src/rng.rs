@@ -0,0 +1,70 @@
+use std;
+
+// The only source of randomness of the engine and the tools, always seeded
+// so that a game or a training run can be reproduced from its printed seed
+
+// `--seed N` on the command line, a seed from the clock otherwise
+pub fn seed_from_args(args: &[String]) -> Result<u64, String> {
+    match args.iter().position(|a| a == "--seed") {
+        Some(i) => args
+            .get(i + 1)
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| "--seed needs a number".to_string()),
+        None => {
+            let t = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_err(|e| e.to_string())?;
+            Ok(t.as_secs() * 1_000_000_000 + t.subsec_nanos() as u64)
+        }
+    }
+}
+
+// xorshift64*, small and good enough for games and training
+#[derive(Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // the state must not be zero
+        let s = seed ^ 0x9E37_79B9_7F4A_7C15;
+        Rng(if s == 0 { 1 } else { s })
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // uniform in 0..n
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    // standard normal, Box-Muller
+    pub fn normal(&mut self) -> f32 {
+        let u = 1.0 - self.next_f32();
+        let v = self.next_f32();
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f32::consts::PI * v).cos()
+    }
+
+    // index drawn with probability proportional to the weights
+    pub fn weighted(&mut self, weights: &[f32]) -> usize {
+        let total: f32 = weights.iter().sum();
+        let mut r = self.next_f32() * total;
+        for (i, &w) in weights.iter().enumerate() {
+            if r < w {
+                return i;
+            }
+            r -= w;
+        }
+        // rounding, the last non zero weight
+        weights.iter().rposition(|&w| w > 0.0).unwrap_or(0)
+    }
+}
@@ -0,0 +1,992 @@
+use book::Book;
+use endgame::Solver;
+use negamax::GameState;
+use network::Network;
+use personality::Personality;
+use rng::Rng;
+use state;
+use state::State;
+use std;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use time;
+use trace::{Reason, Trace};
+
+const MAX_PLY: usize = 64;
+const NO_MOVE: usize = 16;
+// nodes between two checks of the stop flag and of the deadline
+const POLL: u64 = 1024;
+
+// score of a win on the spot, a win in n plies scores WIN - n
+// far above anything State::value can return without a completed line
+pub const WIN: i32 = 1_000_000_000;
+
+// depth of a search without limits in the engine, the server, C and Python
+pub const DEPTH: u32 = 6;
+
+// positions with at most this many empty cells are solved exactly by default
+const ENDGAME: usize = 16;
+
+// half width of the first aspiration window, a three in a row
+const ASPIRATION: i32 = 76 * 76;
+
+// moves searched at full depth before the late move reductions start
+const LMR_MOVES: usize = 3;
+// default plies taken from late moves
+const LMR: u32 = 1;
+// default margin of the futility pruning, two threes in a row
+const FUTILITY: i32 = 2 * 76 * 76;
+
+// the options of Search::configure, each followed by a value
+pub static OPTIONS: [&str; 8] = [
+    "--network",
+    "--endgame",
+    "--trace",
+    "--lmr",
+    "--futility",
+    "--personality",
+    "--book",
+    "--seed",
+];
+
+// centre columns first, then the corners, then the edges
+pub static STATIC_ORDER: [usize; 16] = [5, 6, 9, 10, 0, 3, 12, 15, 1, 2, 4, 7, 8, 11, 13, 14];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    depth: u32,
+    value: i32,
+    bound: Bound,
+    best: usize, // column in the canonical orientation
+}
+
+// the window a node is searched with, its depth and its distance to the root
+#[derive(Clone, Copy)]
+struct Window {
+    depth: u32,
+    alpha: i32,
+    beta: i32,
+    ply: usize,
+}
+
+// evaluation of the leaves of the search
+#[derive(Clone)]
+pub enum Evaluator {
+    Lines, // State::value
+    Network(Arc<Network>),
+}
+
+#[derive(Clone)]
+pub struct Search {
+    table: HashMap<(State, bool), Entry>,
+    evaluator: Evaluator,
+    personality: Personality,
+    // the player to move at the root, whose personality is applied
+    side: i32,
+    killers: [[usize; 2]; MAX_PLY],
+    history: [[u32; 16]; 2],
+    pub ordering: bool,
+    pub pvs: bool,
+    pub aspiration: bool,
+    // plies by which the late quiet moves are reduced, 0 to search them at full depth
+    pub lmr: u32,
+    // quiet moves are skipped near the leaves when the static value plus this margin
+    // does not reach alpha, 0 to disable
+    pub futility: i32,
+    // empty cells under which the game is solved until the end, see endgame.rs
+    pub endgame: usize,
+    // moves learned to lose, never played at the root if anything else is possible
+    pub book: Book,
+    // records the tree of the last root search when set
+    pub trace: Option<Trace>,
+    // breaks the ties between the root moves when set, `--seed N` or the caller,
+    // without it the search is deterministic
+    pub rng: Option<Rng>,
+    // ranks of the columns in the ordering at the root, those of STATIC_ORDER shuffled
+    // by `rng` among the centres, the corners and the edges for each search
+    root_ranks: [usize; 16],
+    // once set, or once `deadline` (time::precise_time_s) is passed, the search
+    // returns the result of the last iteration it completed
+    pub stop: Arc<AtomicBool>,
+    pub deadline: Option<f64>,
+    aborted: bool,
+    // depth of the last search, the empty cells when it was solved until the end
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+fn side(player: i32) -> usize {
+    if player == 1 {
+        0
+    } else {
+        1
+    }
+}
+
+pub fn is_mate(value: i32) -> bool {
+    value.abs() >= WIN - MAX_PLY as i32
+}
+
+// mate scores are stored relative to the node, not to the root
+pub fn to_table(value: i32, ply: usize) -> i32 {
+    if !is_mate(value) {
+        value
+    } else if value > 0 {
+        value + ply as i32
+    } else {
+        value - ply as i32
+    }
+}
+
+pub fn from_table(value: i32, ply: usize) -> i32 {
+    if !is_mate(value) {
+        value
+    } else if value > 0 {
+        value - ply as i32
+    } else {
+        value + ply as i32
+    }
+}
+
+// moves until the end of the game, negative when the value is a loss
+pub fn mate_moves(value: i32) -> Option<i32> {
+    if !is_mate(value) {
+        None
+    } else if value > 0 {
+        Some((WIN - value + 1) / 2)
+    } else {
+        Some(-(WIN + value + 1) / 2)
+    }
+}
+
+// "wins in N" or "loses in N" counted in moves of the side the value belongs to
+pub fn describe(value: i32) -> String {
+    match mate_moves(value) {
+        None => format!("value={}", value),
+        Some(n) if n > 0 => format!("wins in {}", n),
+        Some(n) => format!("loses in {}", -n),
+    }
+}
+
+pub fn print_analysis(analysis: &[(usize, i32)]) {
+    let best = match analysis.first() {
+        Some(&(_, v)) => v,
+        None => return,
+    };
+    for &(col, v) in analysis {
+        if is_mate(v) || is_mate(best) {
+            println!("{} {}", state::format_move(col), describe(v));
+        } else {
+            println!("{} {} ({})", state::format_move(col), describe(v), v - best);
+        }
+    }
+}
+
+fn window_reason(value: i32, alpha: i32, beta: i32) -> Reason {
+    if value >= beta {
+        Reason::Cutoff
+    } else if value <= alpha {
+        Reason::FailLow
+    } else {
+        Reason::Exact
+    }
+}
+
+fn wins_with(x: &State, col: usize, player: i32) -> bool {
+    let mut y = x.clone();
+    y.play(col, player) && y.win(player)
+}
+
+// neither a win, a block nor a threat of winning on the next move
+fn is_quiet(x: &State, col: usize, player: i32) -> bool {
+    if wins_with(x, col, player) || wins_with(x, col, -player) {
+        return false;
+    }
+    let mut y = x.clone();
+    y.play(col, player);
+    (0..16).all(|c| !wins_with(&y, c, player))
+}
+
+impl Default for Search {
+    fn default() -> Search {
+        Search::new()
+    }
+}
+
+impl Search {
+    pub fn new() -> Search {
+        Search {
+            table: HashMap::new(),
+            evaluator: Evaluator::Lines,
+            personality: Personality::balanced(),
+            side: 1,
+            killers: [[NO_MOVE; 2]; MAX_PLY],
+            history: [[0; 16]; 2],
+            ordering: true,
+            pvs: true,
+            aspiration: true,
+            lmr: LMR,
+            futility: FUTILITY,
+            endgame: ENDGAME,
+            book: Book::new(),
+            trace: None,
+            rng: None,
+            root_ranks: [0; 16],
+            stop: Arc::new(AtomicBool::new(false)),
+            deadline: None,
+            aborted: false,
+            depth: 0,
+            nodes: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    // forgets the table and the move ordering heuristics, for a new game
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.killers = [[NO_MOVE; 2]; MAX_PLY];
+        self.history = [[0; 16]; 2];
+    }
+
+    // command line options:
+    // `--network FILE` evaluates with a network instead of the lines
+    // `--endgame N` solves exactly the positions with at most N empty cells
+    // `--trace N` records the search tree up to N plies
+    // `--lmr N` and `--futility N` set the reductions, 0 disables them
+    // `--personality NAME` plays aggressive, defensive or balanced
+    // `--book FILE` never plays the losing moves of FILE, and learns them there
+    // `--seed N` draws the ties between the root moves, see `rng`
+    pub fn configure(&mut self, args: &[String]) -> Result<(), String> {
+        let mut i = 1;
+        while i < args.len() {
+            match args[i].as_str() {
+                "--network" => {
+                    let path = args.get(i + 1).ok_or("--network needs a weights file")?;
+                    let network = Network::load(path)?;
+                    self.set_evaluator(Evaluator::Network(Arc::new(network)));
+                    i += 1;
+                }
+                "--trace" => {
+                    let ply = args
+                        .get(i + 1)
+                        .and_then(|n| n.parse().ok())
+                        .ok_or("--trace needs a depth")?;
+                    self.trace = Some(Trace::new(ply));
+                    i += 1;
+                }
+                "--personality" => {
+                    let name = args.get(i + 1).ok_or("--personality needs a name")?;
+                    self.set_personality(Personality::by_name(name)?);
+                    i += 1;
+                }
+                "--book" => {
+                    self.book = Book::load(args.get(i + 1).ok_or("--book needs a file")?)?;
+                    i += 1;
+                }
+                "--seed" => {
+                    let seed = args
+                        .get(i + 1)
+                        .and_then(|n| n.parse().ok())
+                        .ok_or("--seed needs a number")?;
+                    self.rng = Some(Rng::new(seed));
+                    i += 1;
+                }
+                "--lmr" => {
+                    self.lmr = args
+                        .get(i + 1)
+                        .and_then(|n| n.parse().ok())
+                        .ok_or("--lmr needs a number of plies")?;
+                    i += 1;
+                }
+                "--futility" => {
+                    self.futility = args
+                        .get(i + 1)
+                        .and_then(|n| n.parse().ok())
+                        .ok_or("--futility needs a margin")?;
+                    i += 1;
+                }
+                "--endgame" => {
+                    self.endgame = args
+                        .get(i + 1)
+                        .and_then(|n| n.parse().ok())
+                        .ok_or("--endgame needs a number of empty cells")?;
+                    i += 1;
+                }
+                _ => (),
+            }
+            i += 1;
+        }
+        Ok(())
+    }
+
+    // the options as one string, as a match, the C interface and Python give them, and
+    // returns them as the arguments of configure; unlike configure that skips what it
+    // does not know, a word that is neither in OPTIONS nor in `others` is an error
+    pub fn configure_options(
+        &mut self,
+        options: &str,
+        others: &[&str],
+    ) -> Result<Vec<String>, String> {
+        // configure skips the program name
+        let args: Vec<String> = std::iter::once("")
+            .chain(options.split_whitespace())
+            .map(|a| a.to_string())
+            .collect();
+        let mut i = 1;
+        while i < args.len() {
+            let word = args[i].as_str();
+            if !OPTIONS.contains(&word) && !others.contains(&word) {
+                return Err(format!("unknown option {}", word));
+            }
+            i += 2;
+        }
+        self.configure(&args)?;
+        Ok(args)
+    }
+
+    // the values in the table depend on the evaluator, so it is cleared
+    pub fn set_evaluator(&mut self, evaluator: Evaluator) {
+        self.evaluator = evaluator;
+        self.table.clear();
+    }
+
+    pub fn set_personality(&mut self, personality: Personality) {
+        self.personality = personality;
+        self.table.clear();
+    }
+
+    pub fn personality(&self) -> Personality {
+        self.personality
+    }
+
+    // the key also tells if the engine is to move when its personality makes it matter
+    fn key(&self, x: &State, player: i32) -> ((State, bool), usize) {
+        let (k, id) = state::canonical(x, player);
+        let engine = self.personality.is_symmetric() || player == self.side;
+        ((k, engine), id)
+    }
+
+    // in the perspective of `player`
+    fn evaluate(&self, x: &State, player: i32) -> i32 {
+        match self.evaluator {
+            Evaluator::Lines => {
+                let v = self.personality.lines(x, self.side);
+                if player == self.side {
+                    v
+                } else {
+                    -v
+                }
+            }
+            Evaluator::Network(ref network) => network.value(x, player),
+        }
+    }
+
+    // iterative deepening up to `depth`, returns the best column and its value
+    // in the perspective of `player`
+    pub fn think(&mut self, x: &State, player: i32, depth: u32) -> Option<(usize, i32)> {
+        self.iterate(x, player, depth, |_, _, _| ())
+    }
+
+    // same as think, calls `report` with the best column and its value after each
+    // completed iteration, the depth and the nodes are those of the search
+    pub fn iterate<F>(
+        &mut self,
+        x: &State,
+        player: i32,
+        depth: u32,
+        mut report: F,
+    ) -> Option<(usize, i32)>
+    where
+        F: FnMut(&Search, usize, i32),
+    {
+        // no search goes deeper than the end of the game
+        let depth = depth.min(x.empty() as u32).max(1);
+        self.killers = [[NO_MOVE; 2]; MAX_PLY];
+        for h in self.history.iter_mut() {
+            for v in h.iter_mut() {
+                *v /= 2;
+            }
+        }
+        self.side = player;
+        self.aborted = false;
+        self.depth = 0;
+        self.draw_root_ranks();
+
+        if x.empty() <= self.endgame {
+            let mut solver = Solver::with_personality(self.personality, player);
+            let result = solver.solve(x, player);
+            self.nodes += solver.nodes;
+            self.depth = x.empty() as u32;
+            // the root alone, so that the tree of the previous move is not written again
+            if let Some(ref mut t) = self.trace {
+                t.clear();
+                t.enter(0, player, x.empty() as u32, -i32::MAX, i32::MAX);
+                t.exit(result.map_or(0, |(_, v)| v), Reason::Solved, false);
+            }
+            if let Some((col, v)) = result {
+                report(self, col, v);
+            }
+            return result;
+        }
+
+        let mut result = None;
+        for d in 1..depth + 1 {
+            let r = match result {
+                Some((_, v)) if self.aspiration && !is_mate(v) => self.aspirate(x, player, d, v),
+                _ => self.root(x, player, d, -i32::MAX, i32::MAX),
+            };
+            if self.aborted {
+                break;
+            }
+            result = r;
+            self.depth = d;
+            if let Some((col, v)) = result {
+                report(self, col, v);
+            }
+        }
+        self.aborted = false;
+        result
+    }
+
+    fn draw_root_ranks(&mut self) {
+        for (rank, &col) in STATIC_ORDER.iter().enumerate() {
+            self.root_ranks[col] = 16 - rank;
+        }
+        if let Some(ref mut rng) = self.rng {
+            // Fisher-Yates inside each group of columns exchanged by the symmetries
+            for &(a, b) in [(0, 4), (4, 8), (8, 16)].iter() {
+                for i in (a + 1..b).rev() {
+                    let j = a + rng.below(i - a + 1);
+                    let (ci, cj) = (STATIC_ORDER[i], STATIC_ORDER[j]);
+                    self.root_ranks.swap(ci, cj);
+                }
+            }
+        }
+    }
+
+    // true when the search has to stop, only after the first iteration
+    fn interrupted(&mut self) -> bool {
+        if !self.aborted && self.depth > 0 && self.nodes.is_multiple_of(POLL) {
+            let late = self.deadline.is_some_and(|t| time::precise_time_s() >= t);
+            self.aborted = late || self.stop.load(Ordering::Relaxed);
+        }
+        self.aborted
+    }
+
+    // the moves expected from `col` on, read from the table
+    pub fn pv(&self, x: &State, player: i32, col: usize) -> Vec<usize> {
+        let mut x = x.clone();
+        let mut player = player;
+        let mut col = col;
+        let mut pv = Vec::new();
+        while pv.len() < self.depth as usize && x.play(col, player) {
+            pv.push(col);
+            if x.win(player) || x.full() {
+                break;
+            }
+            player = -player;
+            col = match self.probe_move(&x, player) {
+                Some(col) => col,
+                None => break,
+            };
+        }
+        pv
+    }
+
+    // the engine plays -1, for a hint to +1 the sides are swapped
+    // returns the column for +1 and its value in the perspective of +1
+    pub fn hint(&mut self, x: &State, depth: u32) -> Option<(usize, i32)> {
+        let mut y = x.clone();
+        y.swap();
+        self.without_rng(|s| s.think(&y, -1, depth))
+    }
+
+    // runs `f` without drawing from `rng`, so that hints and analyses do not change
+    // the moves the engine plays with its seed
+    fn without_rng<T, F: FnOnce(&mut Search) -> T>(&mut self, f: F) -> T {
+        let rng = self.rng.take();
+        let r = f(self);
+        self.rng = rng;
+        r
+    }
+
+    // every legal column with its exact value in the perspective of `player`, best first
+    pub fn analyse(&mut self, x: &State, player: i32, depth: u32) -> Vec<(usize, i32)> {
+        let depth = depth.min(x.empty() as u32).max(1);
+        if x.empty() <= self.endgame {
+            let mut solver = Solver::with_personality(self.personality, player);
+            let r = solver.analyse(x, player);
+            self.nodes += solver.nodes;
+            return r;
+        }
+
+        // fills the table and the heuristics so that the full windows below are cheap
+        self.without_rng(|s| s.think(x, player, depth));
+
+        let moves: Vec<usize> = (0..16).filter(|&col| x.playable(col)).collect();
+        let mut r = Vec::new();
+        for class in x.classes(&moves) {
+            let col = class[0];
+            let mut y = x.clone();
+            y.play(col, player);
+            let w = Window {
+                depth,
+                alpha: -i32::MAX,
+                beta: i32::MAX,
+                ply: 0,
+            };
+            let v = self.child(&y, col, player, w, true, 0);
+            // symmetric moves have the same value
+            for col in class {
+                r.push((col, v));
+            }
+        }
+        r.sort_by_key(|&(_, v)| Reverse(v));
+        r
+    }
+
+    // after a lost game, given the positions where `player` moved and its moves,
+    // searches them again at `depth` from the last one and marks in the book the moves
+    // that lose, and those leading to positions where every move loses
+    // returns the number of new moves in the book
+    pub fn learn(&mut self, game: &[(State, usize)], player: i32, depth: u32) -> usize {
+        let mut learned = 0;
+        // the opponent won after the last move
+        let mut lost = true;
+        for &(ref x, col) in game.iter().rev() {
+            if lost && self.book.mark(x, player, col) {
+                learned += 1;
+            }
+            for (c, v) in self.analyse(x, player, depth) {
+                if is_mate(v) && v < 0 && self.book.mark(x, player, c) {
+                    learned += 1;
+                }
+            }
+            lost = (0..16).all(|c| !x.playable(c) || self.book.is_losing(x, player, c));
+        }
+        learned
+    }
+
+    // search a window around the previous iteration's value, widen it on failure
+    fn aspirate(&mut self, x: &State, player: i32, depth: u32, value: i32) -> Option<(usize, i32)> {
+        let mut delta = ASPIRATION;
+        while delta < WIN {
+            let alpha = value - delta;
+            let beta = value + delta;
+            match self.root(x, player, depth, alpha, beta) {
+                _ if self.aborted => return None,
+                Some((col, v)) if v > alpha && v < beta => return Some((col, v)),
+                Some(_) => delta *= 4,
+                None => return None,
+            }
+        }
+        self.root(x, player, depth, -i32::MAX, i32::MAX)
+    }
+
+    fn root(
+        &mut self,
+        x: &State,
+        player: i32,
+        depth: u32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> Option<(usize, i32)> {
+        let alpha0 = alpha;
+        let mut best_value = -i32::MAX;
+        let mut best_move = None;
+
+        if let Some(ref mut t) = self.trace {
+            t.clear();
+            t.enter(0, player, depth, alpha, beta);
+        }
+
+        let tt_move = self.probe_move(x, player);
+
+        let mut moves = self.order(x, player, tt_move, 0);
+        let book = &self.book;
+        if moves.iter().any(|&col| !book.is_losing(x, player, col)) {
+            moves.retain(|&col| !book.is_losing(x, player, col));
+        }
+        // one move per class of symmetric moves
+        let moves: Vec<usize> = x.classes(&moves).into_iter().map(|c| c[0]).collect();
+
+        for col in moves {
+            let mut y = x.clone();
+            y.play(col, player);
+            let first = best_move.is_none();
+            let w = Window {
+                depth,
+                alpha,
+                beta,
+                ply: 0,
+            };
+            let v = self.child(&y, col, player, w, first, 0);
+            if v > best_value {
+                best_value = v;
+                best_move = Some(col);
+            }
+            if v > alpha {
+                alpha = v;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        if let Some(col) = best_move {
+            if best_value > alpha0 && best_value < beta && !self.aborted {
+                let w = Window {
+                    depth,
+                    alpha: alpha0,
+                    beta,
+                    ply: 0,
+                };
+                self.store(x, player, w, best_value, col);
+            }
+        }
+        if let Some(ref mut t) = self.trace {
+            t.exit(best_value, window_reason(best_value, alpha0, beta), tt_move.is_some());
+        }
+        best_move.map(|col| (col, best_value))
+    }
+
+    // value of the child `y` after `player` played `col`, in the perspective of `player`
+    // principal variation search: every move but the first is only proven not to
+    // beat alpha with a null window, and searched again in full when it does
+    // a reduced move is first searched shallower with a null window, and at full depth
+    // only if it beats alpha there
+    fn child(
+        &mut self,
+        y: &State,
+        col: usize,
+        player: i32,
+        w: Window,
+        first: bool,
+        reduction: u32,
+    ) -> i32 {
+        let Window {
+            depth,
+            alpha,
+            beta,
+            ply,
+        } = w;
+        if reduction > 0 && depth > reduction + 1 {
+            self.trace_move(col);
+            let depth = depth - 1 - reduction;
+            let v = -self.negamax(y, -player, depth, -alpha - 1, -alpha, ply + 1);
+            if v <= alpha {
+                return v;
+            }
+        }
+        self.trace_move(col);
+        if first || !self.pvs {
+            return -self.negamax(y, -player, depth - 1, -beta, -alpha, ply + 1);
+        }
+        let v = -self.negamax(y, -player, depth - 1, -alpha - 1, -alpha, ply + 1);
+        if v > alpha && v < beta {
+            self.trace_move(col);
+            -self.negamax(y, -player, depth - 1, -beta, -alpha, ply + 1)
+        } else {
+            v
+        }
+    }
+
+    fn trace_move(&mut self, col: usize) {
+        if let Some(ref mut t) = self.trace {
+            t.set_move(col);
+        }
+    }
+
+    // value in the perspective of `player`, the player to move
+    fn negamax(
+        &mut self,
+        x: &State,
+        player: i32,
+        depth: u32,
+        alpha: i32,
+        beta: i32,
+        ply: usize,
+    ) -> i32 {
+        let traced = match self.trace {
+            Some(ref mut t) => t.enter(ply, player, depth, alpha, beta),
+            None => false,
+        };
+
+        let (value, reason, table_hit) = self.node(x, player, depth, alpha, beta, ply);
+
+        if traced {
+            if let Some(ref mut t) = self.trace {
+                t.exit(value, reason, table_hit);
+            }
+        }
+        value
+    }
+
+    // the value, why it was returned and if the position was in the table
+    fn node(
+        &mut self,
+        x: &State,
+        player: i32,
+        depth: u32,
+        mut alpha: i32,
+        mut beta: i32,
+        ply: usize,
+    ) -> (i32, Reason, bool) {
+        self.nodes += 1;
+        if self.interrupted() {
+            return (0, Reason::Leaf, false);
+        }
+
+        // the last move is the only one that can have completed a line
+        if x.win(-player) {
+            return (-(WIN - ply as i32), Reason::Line, false);
+        }
+        if x.full() {
+            let draw = self.personality.draw(player, self.side);
+            return (draw, Reason::Draw, false);
+        }
+        if depth == 0 {
+            return (self.evaluate(x, player), Reason::Leaf, false);
+        }
+
+        // no line can end faster than the next move
+        alpha = alpha.max(-(WIN - ply as i32));
+        beta = beta.min(WIN - ply as i32 - 1);
+        if alpha >= beta {
+            return (alpha, Reason::MateDistance, false);
+        }
+
+        let alpha0 = alpha;
+        let (k, id) = self.key(x, player);
+        let mut tt_move = None;
+        if let Some(e) = self.table.get(&k) {
+            tt_move = Some(state::symmetry_column(id, e.best));
+            if e.depth >= depth {
+                let value = from_table(e.value, ply);
+                match e.bound {
+                    Bound::Exact => return (value, Reason::Table, true),
+                    Bound::Lower if value >= beta => return (value, Reason::Table, true),
+                    Bound::Upper if value <= alpha => return (value, Reason::Table, true),
+                    _ => (),
+                }
+            }
+        }
+
+        let moves = self.order(x, player, tt_move, ply);
+
+        // near the leaves, quiet moves cannot lift a hopeless static value up to alpha
+        let futile = if self.futility > 0 && depth == 1 && !is_mate(alpha) {
+            let margin = self.evaluate(x, player).saturating_add(self.futility);
+            if margin <= alpha {
+                Some(margin)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        let mut best_value = -i32::MAX;
+        let mut best_move = moves[0];
+        for (i, col) in moves.into_iter().enumerate() {
+            // wins, blocks and threats are never pruned nor reduced
+            let quiet = i > 0
+                && tt_move != Some(col)
+                && (futile.is_some() || i >= LMR_MOVES && self.lmr > 0)
+                && is_quiet(x, col, player);
+            if let (true, Some(margin)) = (quiet, futile) {
+                best_value = best_value.max(margin);
+                continue;
+            }
+            let reduction = if quiet && i >= LMR_MOVES { self.lmr } else { 0 };
+
+            let mut y = x.clone();
+            y.play(col, player);
+            let w = Window {
+                depth,
+                alpha,
+                beta,
+                ply,
+            };
+            let v = self.child(&y, col, player, w, i == 0, reduction);
+            if v > best_value {
+                best_value = v;
+                best_move = col;
+            }
+            if v > alpha {
+                alpha = v;
+            }
+            if alpha >= beta {
+                self.cutoff(player, col, depth, ply);
+                break;
+            }
+        }
+
+        if !self.aborted {
+            let w = Window {
+                depth,
+                alpha: alpha0,
+                beta,
+                ply,
+            };
+            self.store(x, player, w, best_value, best_move);
+        }
+
+        (
+            best_value,
+            window_reason(best_value, alpha0, beta),
+            tt_move.is_some(),
+        )
+    }
+
+    fn cutoff(&mut self, player: i32, col: usize, depth: u32, ply: usize) {
+        if !self.ordering {
+            return;
+        }
+        if ply < MAX_PLY && self.killers[ply][0] != col {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = col;
+        }
+        let h = &mut self.history[side(player)][col];
+        *h = h.saturating_add(depth * depth);
+    }
+
+    fn probe_move(&self, x: &State, player: i32) -> Option<usize> {
+        let (k, id) = self.key(x, player);
+        self.table
+            .get(&k)
+            .map(|e| state::symmetry_column(id, e.best))
+    }
+
+    // the value of the node searched with the window `w`, bounded by the window
+    fn store(&mut self, x: &State, player: i32, w: Window, value: i32, col: usize) {
+        let bound = if value <= w.alpha {
+            Bound::Upper
+        } else if value >= w.beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        let (k, id) = self.key(x, player);
+        self.table.insert(
+            k,
+            Entry {
+                depth: w.depth,
+                value: to_table(value, w.ply),
+                bound,
+                best: state::canonical_column(id, col),
+            },
+        );
+    }
+
+    // legal columns, most promising first:
+    // table move, wins, blocks, killers, history and finally centre first
+    fn order(&self, x: &State, player: i32, tt_move: Option<usize>, ply: usize) -> Vec<usize> {
+        if !self.ordering {
+            // same order as State::possibilities
+            let mut r = Vec::new();
+            for cx in 0..4 {
+                for cy in 0..4 {
+                    if x.playable(cx + 4 * cy) {
+                        r.push(cx + 4 * cy);
+                    }
+                }
+            }
+            return r;
+        }
+
+        let killers = if ply < MAX_PLY {
+            self.killers[ply]
+        } else {
+            [NO_MOVE; 2]
+        };
+
+        let mut scored = Vec::new();
+        for (rank, &col) in STATIC_ORDER.iter().enumerate() {
+            if !x.playable(col) {
+                continue;
+            }
+            let class = if tt_move == Some(col) {
+                5
+            } else if wins_with(x, col, player) {
+                4
+            } else if wins_with(x, col, -player) {
+                3
+            } else if killers[0] == col {
+                2
+            } else if killers[1] == col {
+                1
+            } else {
+                0
+            };
+            let rank = if ply == 0 {
+                self.root_ranks[col]
+            } else {
+                16 - rank
+            };
+            scored.push(((class, self.history[side(player)][col], rank), col));
+        }
+        scored.sort_by_key(|&(key, _)| Reverse(key));
+        scored.into_iter().map(|(_, col)| col).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the search without the reductions, whose values do not depend on the order
+    fn exact() -> Search {
+        let mut search = Search::new();
+        search.lmr = 0;
+        search.futility = 0;
+        search
+    }
+
+    #[test]
+    fn root_classes() {
+        let mut positions = vec![State::new()];
+        for moves in [vec![5], vec![5, 10], vec![0, 15, 5], vec![5, 6, 9, 10]].iter() {
+            let mut x = State::new();
+            let mut player = 1;
+            for &col in moves.iter() {
+                x.play(col, player);
+                player = -player;
+            }
+            positions.push(x);
+        }
+        for x in positions {
+            let player = if (64 - x.empty()) % 2 == 0 { 1 } else { -1 };
+            let depth = 3;
+            // every legal move searched on its own
+            let mut best = -i32::MAX;
+            for col in (0..16).filter(|&col| x.playable(col)) {
+                let mut y = x.clone();
+                y.play(col, player);
+                let (_, v) = exact().think(&y, -player, depth - 1).unwrap();
+                best = best.max(-v);
+            }
+            let (col, v) = exact().think(&x, player, depth).unwrap();
+            assert_eq!(v, best, "{}", x.encode());
+            let mut y = x.clone();
+            y.play(col, player);
+            assert_eq!(-exact().think(&y, -player, depth - 1).unwrap().1, v);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use mcts::Mcts;
+use negamax::GameState;
+use network::{Network, Sample};
+use rng::Rng;
+use search::Search;
+use state;
+use state::State;
+use std::collections::VecDeque;
+
+// AlphaZero style training: self-play games with MCTS guided by the network,
+// a replay buffer of their positions, gradient descent on it and a gating match
+// deciding if the trained network replaces the one playing the games.
+
+// moves played proportionally to the visit counts at the start of self-play games
+const TEMPERATURE_PLIES: usize = 8;
+// same thing for the evaluation games, only to avoid playing twice the same game
+const EVALUATION_PLIES: usize = 2;
+
+pub struct Config {
+    pub hidden: Vec<usize>,
+    pub iterations: u32,
+    pub games: u32, // self-play games per iteration
+    pub simulations: u32,
+    pub noise: f32,
+    pub buffer: usize, // positions kept in the replay buffer
+    pub batch: usize,
+    pub steps: u32, // gradient steps per iteration
+    pub rate: f32,
+    pub decay: f32,
+    pub gating_games: u32,
+    pub gating_score: f32, // fraction of the points needed to replace the network
+    pub negamax_games: u32,
+    pub negamax_depth: u32,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::new()
+    }
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config {
+            hidden: vec![64, 64],
+            iterations: 20,
+            games: 20,
+            simulations: 100,
+            noise: 0.25,
+            buffer: 20000,
+            batch: 64,
+            steps: 500,
+            rate: 0.01,
+            decay: 1e-4,
+            gating_games: 20,
+            gating_score: 0.55,
+            negamax_games: 10,
+            negamax_depth: 2,
+        }
+    }
+}
+
+pub struct ReplayBuffer {
+    samples: VecDeque<Sample>,
+    capacity: usize,
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize) -> ReplayBuffer {
+        ReplayBuffer {
+            samples: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn push(&mut self, sample: Sample) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    // a random position under a random symmetry
+    pub fn draw(&self, rng: &mut Rng) -> Sample {
+        let sample = &self.samples[rng.below(self.samples.len())];
+        symmetry(sample, rng.below(8))
+    }
+}
+
+fn symmetry(sample: &Sample, id: usize) -> Sample {
+    let mut policy = [0.0; 16];
+    for (col, p) in policy.iter_mut().enumerate() {
+        *p = sample.policy[state::symmetry_column(id, col)];
+    }
+    Sample {
+        state: sample.state.symmetry(id),
+        policy,
+        value: sample.value,
+    }
+}
+
+// the position seen by the player to move, as the network sees it
+fn normalized(x: &State, player: i32) -> State {
+    let mut x = x.clone();
+    if player == -1 {
+        x.swap();
+    }
+    x
+}
+
+fn winner(x: &State) -> i32 {
+    if x.win(1) {
+        1
+    } else if x.win(-1) {
+        -1
+    } else {
+        0
+    }
+}
+
+fn game_over(x: &State) -> bool {
+    winner(x) != 0 || x.full()
+}
+
+fn argmax(pi: &[f32; 16]) -> usize {
+    let mut best = 0;
+    for col in 1..16 {
+        if pi[col] > pi[best] {
+            best = col;
+        }
+    }
+    best
+}
+
+pub fn self_play(network: &Network, config: &Config, rng: &mut Rng) -> Vec<Sample> {
+    let mut mcts = Mcts::new(network, config.simulations);
+    mcts.noise = config.noise;
+
+    let mut x = State::new();
+    let mut player = 1;
+    let mut positions = Vec::new();
+
+    while !game_over(&x) {
+        let pi = mcts.search(&x, player, rng);
+        positions.push((normalized(&x, player), pi, player));
+
+        let col = if positions.len() <= TEMPERATURE_PLIES {
+            rng.weighted(&pi)
+        } else {
+            argmax(&pi)
+        };
+        x.play(col, player);
+        player = -player;
+    }
+
+    let z = winner(&x);
+    positions
+        .into_iter()
+        .map(|(state, policy, player)| Sample {
+            state,
+            policy,
+            value: (z * player) as f32,
+        })
+        .collect()
+}
+
+pub enum Agent<'a> {
+    Mcts(&'a Network, u32),
+    Negamax(u32),
+}
+
+// +1 if `first` wins, -1 if `second` wins, `first` plays +1
+fn play_game(first: &Agent, second: &Agent, rng: &mut Rng) -> i32 {
+    let mut x = State::new();
+    let mut player = 1;
+    let mut ply = 0;
+    let mut searches = [Search::new(), Search::new()];
+
+    while !game_over(&x) {
+        let i = if player == 1 { 0 } else { 1 };
+        let agent = if player == 1 { first } else { second };
+        let col = match *agent {
+            Agent::Mcts(network, simulations) => {
+                let pi = Mcts::new(network, simulations).search(&x, player, rng);
+                if ply < EVALUATION_PLIES {
+                    rng.weighted(&pi)
+                } else {
+                    argmax(&pi)
+                }
+            }
+            Agent::Negamax(depth) => match searches[i].think(&x, player, depth) {
+                Some((col, _)) => col,
+                None => break,
+            },
+        };
+        x.play(col, player);
+        player = -player;
+        ply += 1;
+    }
+
+    winner(&x)
+}
+
+// wins, draws and losses of `a` against `b`, alternating who starts
+pub fn match_score(a: &Agent, b: &Agent, games: u32, rng: &mut Rng) -> (u32, u32, u32) {
+    let mut wdl = (0, 0, 0);
+    for g in 0..games {
+        let r = if g % 2 == 0 {
+            play_game(a, b, rng)
+        } else {
+            -play_game(b, a, rng)
+        };
+        match r {
+            1 => wdl.0 += 1,
+            0 => wdl.1 += 1,
+            _ => wdl.2 += 1,
+        }
+    }
+    wdl
+}
+
+fn points(wdl: (u32, u32, u32)) -> f32 {
+    let games = wdl.0 + wdl.1 + wdl.2;
+    (wdl.0 as f32 + 0.5 * wdl.1 as f32) / games.max(1) as f32
+}
+
+// runs the training loop, the best network is saved to `out` each time it changes
+pub fn train(config: &Config, network: Network, out: &str, rng: &mut Rng) {
+    let mut best = network;
+    let mut candidate = best.clone();
+    let mut buffer = ReplayBuffer::new(config.buffer);
+
+    for iteration in 0..config.iterations {
+        for _ in 0..config.games {
+            for sample in self_play(&best, config, rng) {
+                buffer.push(sample);
+            }
+        }
+
+        // nothing to learn from before the first game, `--games 0` for instance
+        let steps = if buffer.is_empty() { 0 } else { config.steps };
+        let mut loss = 0.0;
+        for _ in 0..steps {
+            let batch: Vec<Sample> = (0..config.batch).map(|_| buffer.draw(rng)).collect();
+            let batch: Vec<&Sample> = batch.iter().collect();
+            loss += candidate.train(&batch, config.rate, config.decay);
+        }
+        println!(
+            "iteration {} {} positions loss={:.4}",
+            iteration,
+            buffer.len(),
+            loss / steps.max(1) as f32
+        );
+
+        let wdl = match_score(
+            &Agent::Mcts(&candidate, config.simulations),
+            &Agent::Mcts(&best, config.simulations),
+            config.gating_games,
+            rng,
+        );
+        if points(wdl) >= config.gating_score {
+            println!("candidate {}/{}/{} accepted", wdl.0, wdl.1, wdl.2);
+            best = candidate.clone();
+            if let Err(e) = best.save(out) {
+                println!("{}: {}", out, e);
+            }
+        } else {
+            println!("candidate {}/{}/{} rejected", wdl.0, wdl.1, wdl.2);
+        }
+
+        if config.negamax_games > 0 {
+            let wdl = match_score(
+                &Agent::Mcts(&best, config.simulations),
+                &Agent::Negamax(config.negamax_depth),
+                config.negamax_games,
+                rng,
+            );
+            println!(
+                "against negamax depth {}: {}/{}/{}",
+                config.negamax_depth, wdl.0, wdl.1, wdl.2
+            );
+        }
+    }
+}
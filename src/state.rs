@@ -1,4 +1,5 @@
 use negamax;
+use negamax::GameState;
 use std;
 use std::cmp::Ordering;
 
@@ -25,6 +26,12 @@ impl PartialEq for State {
 
 impl Eq for State {}
 
+impl std::hash::Hash for State {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
 impl PartialOrd for State {
     fn partial_cmp(&self, other: &State) -> Option<Ordering> {
         for i in 0..4 * 4 * 4 {
@@ -51,6 +58,12 @@ impl Ord for State {
     }
 }
 
+impl Default for State {
+    fn default() -> State {
+        State::new()
+    }
+}
+
 impl State {
     pub fn new() -> State {
         State([0; 4 * 4 * 4])
@@ -62,6 +75,11 @@ impl State {
         self.0[x + 4 * y + 16 * z]
     }
 
+    // all the cells, index x + 4 * y + 16 * z
+    pub fn cells(&self) -> &[i32; 4 * 4 * 4] {
+        &self.0
+    }
+
     pub fn add(&mut self, x: usize, y: usize, player: i32) -> bool {
         for z in 0..4 {
             if self.get(x, y, z) == 0 {
@@ -72,6 +90,23 @@ impl State {
         false
     }
 
+    // column index: x + 4 * y
+    pub fn playable(&self, col: usize) -> bool {
+        self.0[col + 16 * 3] == 0
+    }
+
+    pub fn play(&mut self, col: usize, player: i32) -> bool {
+        self.add(col % 4, col / 4, player)
+    }
+
+    pub fn empty(&self) -> usize {
+        self.0.iter().filter(|&&c| c == 0).count()
+    }
+
+    pub fn full(&self) -> bool {
+        (0..16).all(|col| !self.playable(col))
+    }
+
     pub fn symmetry(&self, id: usize) -> State {
         let mut x = State::new();
 
@@ -83,18 +118,76 @@ impl State {
 
         x
     }
-}
 
-impl<'a> negamax::GameState<'a> for State {
-    type It = Vec<State>;
+    // the 16 columns x + 4 * y separated by '/', each from bottom to top
+    // with '+', '-' or '.' for an empty cell, see FromStr for the reverse
+    pub fn encode(&self) -> String {
+        let mut s = String::new();
+        for col in 0..16 {
+            if col > 0 {
+                s.push('/');
+            }
+            for z in 0..4 {
+                s.push(match self.0[col + 16 * z] {
+                    1 => '+',
+                    -1 => '-',
+                    _ => '.',
+                });
+            }
+        }
+        s
+    }
 
-    // compute the value in player +1 perspective
-    fn value(&self) -> i32 {
+    // representative of the position up to the 8 symmetries
+    // returns it with the id of the symmetry that maps self onto it
+    pub fn canonical(&self) -> (State, usize) {
+        let mut best = self.clone();
+        let mut best_id = 0;
+        for id in 1..8 {
+            let x = self.symmetry(id);
+            if x < best {
+                best = x;
+                best_id = id;
+            }
+        }
+        (best, best_id)
+    }
+
+    // ids of the symmetries leaving the position unchanged, the identity first
+    pub fn stabiliser(&self) -> Vec<usize> {
+        (0..8)
+            .filter(|&id| id == 0 || self.symmetry(id) == *self)
+            .collect()
+    }
+
+    // `moves` grouped in classes of columns exchanged by the stabiliser, in the order of
+    // their first move; the moves of a class lead to symmetric positions
+    pub fn classes(&self, moves: &[usize]) -> Vec<Vec<usize>> {
+        let stabiliser = self.stabiliser();
+        let mut classes: Vec<Vec<usize>> = Vec::new();
+        for &col in moves {
+            let class = classes.iter().position(|class| {
+                stabiliser
+                    .iter()
+                    .any(|&id| symmetry_column(id, class[0]) == col)
+            });
+            match class {
+                Some(i) => classes[i].push(col),
+                None => classes.push(vec![col]),
+            }
+        }
+        classes
+    }
+
+    // value of the lines still open to +1 and of those still open to -1, see value
+    pub fn lines(&self) -> (i32, i32) {
         // 1        - 1 on a row
         // 76       - 2 on a row
         // 76*76    - 3 on a row
         // 76*76*76 - 4 on a row
-        let mut v = 0;
+        let weights = [0, 1, 76, 76 * 76, 76 * 76 * 76];
+        let mut plus = 0;
+        let mut minus = 0;
 
         for line in LINES.iter() {
             let mut me = 0;
@@ -108,35 +201,69 @@ impl<'a> negamax::GameState<'a> for State {
                 }
             }
             if op == 0 {
-                if me == 1 {
-                    v += 1;
-                }
-                if me == 2 {
-                    v += 76;
-                }
-                if me == 3 {
-                    v += 76 * 76;
-                }
-                if me == 4 {
-                    v += 76 * 76 * 76;
-                }
+                plus += weights[me];
             }
             if me == 0 {
-                if op == 1 {
-                    v -= 1;
-                }
-                if op == 2 {
-                    v -= 76;
-                }
-                if op == 3 {
-                    v -= 76 * 76;
-                }
-                if op == 4 {
-                    v -= 76 * 76 * 76;
-                }
+                minus += weights[op];
             }
         }
-        v
+        (plus, minus)
+    }
+}
+
+// column `col` of self.symmetry(id) is column symmetry_column(id, col) of self
+pub fn symmetry_column(id: usize, col: usize) -> usize {
+    SYMMETRIES[id][col]
+}
+
+// the canonical position with the player to move as +1 and the id of the symmetry
+// leading to it, the key of the tables of the search, of the endgame solver and of
+// the book
+pub fn canonical(x: &State, player: i32) -> (State, usize) {
+    if player == 1 {
+        x.canonical()
+    } else {
+        let mut x = x.clone();
+        x.swap();
+        x.canonical()
+    }
+}
+
+// the column of x.symmetry(id) that is column `col` of x, the reverse of symmetry_column
+pub fn canonical_column(id: usize, col: usize) -> usize {
+    (0..16)
+        .find(|&c| symmetry_column(id, c) == col)
+        .expect("the symmetries exchange the columns")
+}
+
+// move in the "xy" notation, 11 to 44, into a column index
+pub fn parse_move(s: &str) -> Option<usize> {
+    let mov: usize = match s.trim().parse() {
+        Ok(num) => num,
+        Err(_) => return None,
+    };
+
+    let mx = mov / 10;
+    let my = mov % 10;
+
+    if (1..=4).contains(&mx) && (1..=4).contains(&my) {
+        Some((mx - 1) + 4 * (my - 1))
+    } else {
+        None
+    }
+}
+
+pub fn format_move(col: usize) -> String {
+    format!("{}{}", col % 4 + 1, col / 4 + 1)
+}
+
+impl<'a> negamax::GameState<'a> for State {
+    type It = Vec<State>;
+
+    // compute the value in player +1 perspective
+    fn value(&self) -> i32 {
+        let (plus, minus) = self.lines();
+        plus - minus
     }
 
     fn possibilities(&self, player: i32) -> Vec<State> {
@@ -180,6 +307,38 @@ impl<'a> negamax::GameState<'a> for State {
     }
 }
 
+impl std::str::FromStr for State {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<State, String> {
+        let columns: Vec<&str> = s.trim().split('/').collect();
+        if columns.len() != 16 {
+            return Err(format!("expected 16 columns, got {}", columns.len()));
+        }
+        let mut x = State::new();
+        for (col, cells) in columns.iter().enumerate() {
+            if cells.len() != 4 {
+                return Err(format!("column {} must have 4 cells", col));
+            }
+            let mut top = false;
+            for (z, c) in cells.chars().enumerate() {
+                let player = match c {
+                    '+' => 1,
+                    '-' => -1,
+                    '.' => 0,
+                    _ => return Err(format!("invalid cell '{}'", c)),
+                };
+                if player != 0 && top {
+                    return Err(format!("column {} has a piece above an empty cell", col));
+                }
+                top = player == 0;
+                x.0[col + 16 * z] = player;
+            }
+        }
+        Ok(x)
+    }
+}
+
 impl std::fmt::Display for State {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let mut s = String::new();
@@ -350,3 +509,84 @@ static SYMMETRIES: [[usize; 16]; 8] = [
         15, 11, 07, 03, 14, 10, 06, 02, 13, 09, 05, 01, 12, 08, 04, 00,
     ],
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rng::Rng;
+
+    // the positions of a random game, until a win or the full board
+    fn random_game(seed: u64) -> Vec<State> {
+        let mut rng = Rng::new(seed);
+        let mut x = State::new();
+        let mut player = 1;
+        let mut positions = vec![x.clone()];
+        while !x.full() && !x.win(-player) {
+            let moves: Vec<usize> = (0..16).filter(|&col| x.playable(col)).collect();
+            x.play(moves[rng.below(moves.len())], player);
+            positions.push(x.clone());
+            player = -player;
+        }
+        positions
+    }
+
+    #[test]
+    fn encode_parse() {
+        for seed in 0..10 {
+            for x in random_game(seed) {
+                assert!(x.encode().parse::<State>() == Ok(x.clone()));
+            }
+        }
+        let empty = "..../".repeat(15) + "....";
+        assert!(empty.parse::<State>() == Ok(State::new()));
+        let floating = ".+../".to_string() + &"..../".repeat(14) + "....";
+        assert!(floating.parse::<State>().is_err());
+        assert!("..../....".parse::<State>().is_err());
+        assert!(empty.replace("....", "...x").parse::<State>().is_err());
+    }
+
+    #[test]
+    fn canonical() {
+        for x in random_game(1) {
+            for &player in [1, -1].iter() {
+                let (k, id) = super::canonical(&x, player);
+                let mut y = x.clone();
+                if player == -1 {
+                    y.swap();
+                }
+                assert!(y.symmetry(id) == k, "{}", x.encode());
+                for col in 0..16 {
+                    let c = canonical_column(id, col);
+                    assert_eq!(symmetry_column(id, c), col);
+                    assert_eq!(k.playable(c), x.playable(col));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn classes() {
+        let mut x = State::new();
+        let moves: Vec<usize> = (0..16).collect();
+        // the centres, the corners and the edges
+        assert_eq!(x.classes(&moves).len(), 3);
+        x.play(0, 1);
+        x.play(15, -1);
+        for seed in 0..10 {
+            for x in random_game(seed).into_iter().chain(Some(x.clone())) {
+                let moves: Vec<usize> = (0..16).filter(|&col| x.playable(col)).collect();
+                let classes = x.classes(&moves);
+                assert_eq!(classes.iter().map(|c| c.len()).sum::<usize>(), moves.len());
+                for class in classes.iter() {
+                    let mut y = x.clone();
+                    y.play(class[0], 1);
+                    for &col in class.iter() {
+                        let mut z = x.clone();
+                        z.play(col, 1);
+                        assert!(z.canonical().0 == y.canonical().0, "{}", x.encode());
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use arena;
+use arena::{Engine, Game, Limits, Score};
+use bench::Position;
+use std;
+use std::collections::VecDeque;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+
+// Tournaments between many engines
+//
+// Each round pairs the engines, a pairing being two games from the same opening with
+// the colors swapped. Round robin pairs everyone once per cycle, Swiss pairs the
+// engines of close scores who did not meet yet. The games of a round run in parallel.
+
+pub struct Player {
+    pub name: String,
+    // as described in arena.rs
+    pub spec: String,
+}
+
+impl Player {
+    // NAME=SPEC
+    pub fn parse(arg: &str) -> Result<Player, String> {
+        match arg.find('=') {
+            Some(i) if i > 0 => Ok(Player {
+                name: arg[..i].to_string(),
+                spec: arg[i + 1..].to_string(),
+            }),
+            _ => Err(format!("{}: expected NAME=SPEC", arg)),
+        }
+    }
+}
+
+// one line per engine: its name and its spec, `#` starts a comment
+pub fn load_players(path: &str) -> Result<Vec<Player>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut players = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (name, spec) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+        if players.iter().any(|p: &Player| p.name == name) {
+            return Err(format!("{} line {}: {} twice", path, n + 1, name));
+        }
+        players.push(Player {
+            name: name.to_string(),
+            spec: spec.to_string(),
+        });
+    }
+    Ok(players)
+}
+
+#[derive(Clone)]
+pub struct Round {
+    pub pairs: Vec<(usize, usize)>,
+    pub bye: Option<usize>,
+}
+
+// the rounds of a cycle of round robin, by the circle method
+pub fn round_robin(n: usize) -> Vec<Round> {
+    // with an odd number, the player paired with `n` has a bye
+    let m = n + n % 2;
+    let mut circle: Vec<usize> = (0..m).collect();
+    let mut rounds = Vec::new();
+    for r in 0..m - 1 {
+        let mut round = Round {
+            pairs: Vec::new(),
+            bye: None,
+        };
+        for i in 0..m / 2 {
+            let (a, b) = (circle[i], circle[m - 1 - i]);
+            // the first player alternates so that nobody always starts the pairings
+            let (a, b) = if (r + i) % 2 == 0 { (a, b) } else { (b, a) };
+            if a == n {
+                round.bye = Some(b);
+            } else if b == n {
+                round.bye = Some(a);
+            } else {
+                round.pairs.push((a, b));
+            }
+        }
+        // the first stays, the others turn
+        let last = circle.pop().expect("two players at least");
+        circle.insert(1, last);
+        rounds.push(round);
+    }
+    rounds
+}
+
+// results of the tournament so far
+pub struct Table {
+    // score of i against j
+    pub scores: Vec<Vec<Score>>,
+    pub byes: Vec<u32>,
+}
+
+impl Table {
+    pub fn new(n: usize) -> Table {
+        Table {
+            scores: vec![vec![Score::default(); n]; n],
+            byes: vec![0; n],
+        }
+    }
+
+    // a game where `plus` played +
+    pub fn add(&mut self, plus: usize, minus: usize, game: &Game) {
+        self.scores[plus][minus].add(game.result);
+        self.scores[minus][plus].add(-game.result);
+    }
+
+    // a bye counts as a drawn pairing
+    pub fn points(&self, i: usize) -> f64 {
+        self.scores[i].iter().map(Score::points).sum::<f64>() + self.byes[i] as f64
+    }
+
+    pub fn games(&self, i: usize) -> u32 {
+        self.scores[i].iter().map(Score::games).sum()
+    }
+
+    // the players by decreasing points, then by rating
+    pub fn standings(&self) -> Vec<usize> {
+        let ratings = self.ratings();
+        let mut order: Vec<usize> = (0..self.byes.len()).collect();
+        order.sort_by(|&a, &b| {
+            (self.points(b), ratings[b])
+                .partial_cmp(&(self.points(a), ratings[a]))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        order
+    }
+
+    // Elo ratings of maximum likelihood, their mean is 0, every player has a virtual
+    // draw against a player of rating 0 so that a perfect score stays finite
+    pub fn ratings(&self) -> Vec<f64> {
+        let n = self.byes.len();
+        let mut r = vec![0.0; n];
+        for _ in 0..1000 {
+            let mut next = r.clone();
+            for i in 0..n {
+                let mut points = 0.5;
+                let mut expected = expected_score(r[i]);
+                let mut games = 1.0;
+                for j in 0..n {
+                    let s = &self.scores[i][j];
+                    points += s.points();
+                    expected += s.games() as f64 * expected_score(r[i] - r[j]);
+                    games += s.games() as f64;
+                }
+                // a step of Newton, the variance of a game taken as 1/4: 4 * 400 / ln 10
+                next[i] += 695.0 * (points - expected) / games;
+            }
+            let mean = next.iter().sum::<f64>() / n as f64;
+            r = next.into_iter().map(|x| x - mean).collect();
+        }
+        r
+    }
+
+    // Swiss pairing: from the top of the standings, each one against the next one not
+    // met yet, or just the next one when all were met. With an odd number the lowest
+    // one among those with the fewest byes has the bye.
+    pub fn swiss(&self) -> Round {
+        let order = self.standings();
+        let fewest = order.iter().map(|&i| self.byes[i]).min().unwrap_or(0);
+        let bye = if order.len() % 2 == 1 {
+            order
+                .iter()
+                .rev()
+                .cloned()
+                .find(|&i| self.byes[i] == fewest)
+        } else {
+            None
+        };
+        let mut left: Vec<usize> = order.into_iter().filter(|&i| Some(i) != bye).collect();
+        let mut pairs = Vec::new();
+        while !left.is_empty() {
+            let a = left.remove(0);
+            let k = left
+                .iter()
+                .position(|&b| self.scores[a][b].games() == 0)
+                .unwrap_or(0);
+            let b = left.remove(k);
+            pairs.push((a, b));
+        }
+        Round { pairs, bye }
+    }
+}
+
+// expected score for a difference of rating
+fn expected_score(diff: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-diff / 400.0))
+}
+
+// a game to play: the players of + and -, and the opening
+pub struct Job {
+    pub plus: usize,
+    pub minus: usize,
+    pub opening: usize,
+}
+
+// plays the games on `threads` threads, `done` receives each game as it ends,
+// every game has its own engines so that no state is shared between threads
+pub fn play<F>(
+    players: &[Player],
+    openings: &[Position],
+    limits: Limits,
+    jobs: Vec<Job>,
+    threads: usize,
+    mut done: F,
+) -> Result<(), String>
+where
+    F: FnMut(&Job, Game),
+{
+    let specs: Arc<Vec<String>> = Arc::new(players.iter().map(|p| p.spec.clone()).collect());
+    let openings = Arc::new(openings.to_vec());
+    let queue = Arc::new(Mutex::new(jobs.into_iter().collect::<VecDeque<Job>>()));
+    let (sender, games) = channel();
+
+    let mut workers = Vec::new();
+    for _ in 0..threads.max(1) {
+        let (specs, openings, queue, sender) = (
+            specs.clone(),
+            openings.clone(),
+            queue.clone(),
+            sender.clone(),
+        );
+        workers.push(std::thread::spawn(move || loop {
+            let job = match queue.lock().unwrap().pop_front() {
+                Some(job) => job,
+                None => return,
+            };
+            let result = Engine::from_spec(&specs[job.plus], limits).and_then(|mut plus| {
+                let mut minus = Engine::from_spec(&specs[job.minus], limits)?;
+                Ok(arena::play(&mut plus, &mut minus, &openings[job.opening]))
+            });
+            if sender.send((job, result)).is_err() {
+                return;
+            }
+        }));
+    }
+    drop(sender);
+
+    let mut error = None;
+    for (job, result) in games {
+        match result {
+            Ok(game) => done(&job, game),
+            Err(e) => {
+                // the other workers stop at their next job
+                queue.lock().unwrap().clear();
+                error = Some(e);
+            }
+        }
+    }
+    for w in workers {
+        w.join().expect("worker panicked");
+    }
+    match error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+// the players from the top of the standings with their ratings and points
+pub fn print_ratings(players: &[Player], table: &Table) {
+    let ratings = table.ratings();
+    println!("rank name                 elo  points  games  score");
+    for (rank, &i) in table.standings().iter().enumerate() {
+        let games = table.games(i);
+        let points = table.points(i) - table.byes[i] as f64;
+        println!(
+            "{:4} {:16} {:+7.0} {:7.1} {:6} {:5.1}%",
+            rank + 1,
+            players[i].name,
+            ratings[i],
+            table.points(i),
+            games,
+            100.0 * points / games.max(1) as f64
+        );
+    }
+}
+
+// points of each player, in the order of the standings, against each other
+pub fn print_crosstable(players: &[Player], table: &Table) {
+    let order = table.standings();
+    print!("{:16}", "");
+    for k in 0..order.len() {
+        print!(" {:>7}", k + 1);
+    }
+    println!("  points");
+    for (k, &i) in order.iter().enumerate() {
+        print!("{:2} {:13}", k + 1, players[i].name);
+        for &j in order.iter() {
+            let s = &table.scores[i][j];
+            if i == j {
+                print!(" {:>7}", "x");
+            } else if s.games() == 0 {
+                print!(" {:>7}", ".");
+            } else {
+                print!(" {:>7}", format!("{}/{}", s.points(), s.games()));
+            }
+        }
+        println!("  {:.1}", table.points(i));
+    }
+}
@@ -0,0 +1,204 @@
+use state;
+use std;
+use std::io::Write;
+
+// Record of the tree explored by the search, for debugging
+//
+// Only the nodes up to `max_ply` are recorded, each new root search starts a new tree.
+// The tree can be written in Graphviz DOT (`dot -Tsvg search.dot`) and in JSON.
+
+// why a node returned its value
+#[derive(Clone, Copy, PartialEq)]
+pub enum Reason {
+    Line,         // the last move completed a line
+    Draw,         // full board
+    Leaf,         // depth exhausted, static evaluation
+    MateDistance, // the window is out of reach of any win or loss
+    Table,        // transposition table cutoff
+    Cutoff,       // beta cutoff
+    FailLow,      // no move reached alpha
+    Exact,        // value within the window
+    Solved,       // endgame solver, the tree below is not recorded
+}
+
+impl Reason {
+    fn name(&self) -> &'static str {
+        match *self {
+            Reason::Line => "line",
+            Reason::Draw => "draw",
+            Reason::Leaf => "leaf",
+            Reason::MateDistance => "mate distance",
+            Reason::Table => "table",
+            Reason::Cutoff => "cutoff",
+            Reason::FailLow => "fail low",
+            Reason::Exact => "exact",
+            Reason::Solved => "solved",
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Node {
+    col: Option<usize>, // move leading to the node, None for a root
+    player: i32,        // player to move
+    depth: u32,
+    alpha: i32,
+    beta: i32,
+    value: i32,
+    reason: Reason,
+    table_hit: bool,
+    children: Vec<usize>,
+}
+
+#[derive(Clone)]
+pub struct Trace {
+    pub max_ply: usize,
+    nodes: Vec<Node>,
+    roots: Vec<usize>,
+    stack: Vec<usize>,
+    next_move: Option<usize>,
+}
+
+fn bound(v: i32) -> String {
+    if v == i32::MAX {
+        "+inf".to_string()
+    } else if v == -i32::MAX {
+        "-inf".to_string()
+    } else {
+        v.to_string()
+    }
+}
+
+impl Trace {
+    pub fn new(max_ply: usize) -> Trace {
+        Trace {
+            max_ply,
+            nodes: Vec::new(),
+            roots: Vec::new(),
+            stack: Vec::new(),
+            next_move: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.roots.clear();
+        self.stack.clear();
+        self.next_move = None;
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // the move leading to the next node entered
+    pub fn set_move(&mut self, col: usize) {
+        self.next_move = Some(col);
+    }
+
+    // returns false if the node is too deep to be recorded
+    pub fn enter(&mut self, ply: usize, player: i32, depth: u32, alpha: i32, beta: i32) -> bool {
+        let col = self.next_move.take();
+        if ply > self.max_ply {
+            return false;
+        }
+        self.nodes.push(Node {
+            col,
+            player,
+            depth,
+            alpha,
+            beta,
+            value: 0,
+            reason: Reason::Exact,
+            table_hit: false,
+            children: Vec::new(),
+        });
+        let id = self.nodes.len() - 1;
+        match self.stack.last() {
+            Some(&parent) => self.nodes[parent].children.push(id),
+            None => self.roots.push(id),
+        }
+        self.stack.push(id);
+        true
+    }
+
+    // closes the node opened by the last successful enter
+    pub fn exit(&mut self, value: i32, reason: Reason, table_hit: bool) {
+        if let Some(id) = self.stack.pop() {
+            let node = &mut self.nodes[id];
+            node.value = value;
+            node.reason = reason;
+            node.table_hit = table_hit;
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut s = String::from("digraph search {\n    node [shape=box fontname=monospace];\n");
+        for (id, node) in self.nodes.iter().enumerate() {
+            let col = node.col.map_or("root".to_string(), state::format_move);
+            s.push_str(&format!(
+                "    n{} [label=\"{} ({})\\n[{}, {}] d={}\\n{} {}\"{}];\n",
+                id,
+                col,
+                if node.player == 1 { "+" } else { "-" },
+                bound(node.alpha),
+                bound(node.beta),
+                node.depth,
+                node.value,
+                node.reason.name(),
+                if node.table_hit { " color=blue" } else { "" }
+            ));
+            for &c in node.children.iter() {
+                s.push_str(&format!("    n{} -> n{};\n", id, c));
+            }
+        }
+        s.push_str("}\n");
+        s
+    }
+
+    fn node_json(&self, id: usize, s: &mut String) {
+        let node = &self.nodes[id];
+        s.push_str(&format!(
+            "{{\"move\":{},\"player\":{},\"depth\":{},\"alpha\":{},\"beta\":{},\"value\":{},\"reason\":\"{}\",\"table_hit\":{},\"children\":[",
+            node.col.map_or("null".to_string(), |c| format!("\"{}\"", state::format_move(c))),
+            node.player,
+            node.depth,
+            node.alpha,
+            node.beta,
+            node.value,
+            node.reason.name(),
+            node.table_hit
+        ));
+        for (i, &c) in node.children.iter().enumerate() {
+            if i > 0 {
+                s.push(',');
+            }
+            self.node_json(c, s);
+        }
+        s.push_str("]}");
+    }
+
+    // an array of the recorded trees
+    pub fn to_json(&self) -> String {
+        let mut s = String::from("[");
+        for (i, &root) in self.roots.iter().enumerate() {
+            if i > 0 {
+                s.push(',');
+            }
+            self.node_json(root, &mut s);
+        }
+        s.push_str("]\n");
+        s
+    }
+
+    // writes `name`.dot and `name`.json
+    pub fn save(&self, name: &str) -> std::io::Result<()> {
+        std::fs::File::create(format!("{}.dot", name))?.write_all(self.to_dot().as_bytes())?;
+        std::fs::File::create(format!("{}.json", name))?.write_all(self.to_json().as_bytes())?;
+        Ok(())
+    }
+}